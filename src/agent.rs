@@ -1,16 +1,32 @@
-use std::collections::HashSet;
+use std::{
+    collections::{HashMap, HashSet},
+    fmt::Display,
+};
 
-use crate::{env::Sense, grid::Pos};
+use crate::{
+    env::{ActionResult, Environment, Sense},
+    error::Error,
+    grid::Pos,
+    kb::KbStats,
+    logic::Statement,
+};
 
+/// One of the four directions of the grid, north pointing
+/// towards its first row.
 #[derive(Eq, Debug, PartialEq, Hash, Clone)]
 pub enum Direction {
+    /// Towards the first row
     North,
+    /// Towards the last row
     South,
+    /// Towards the last column
     East,
+    /// Towards the first column
     West,
 }
 
 impl Direction {
+    /// The opposite direction.
     pub fn reverse(&self) -> Self {
         match self {
             Self::North => Self::South,
@@ -19,72 +35,388 @@ impl Direction {
             Self::West => Self::East,
         }
     }
+
+    /// The direction faced after turning left.
+    pub fn left(&self) -> Self {
+        match self {
+            Self::North => Self::West,
+            Self::West => Self::South,
+            Self::South => Self::East,
+            Self::East => Self::North,
+        }
+    }
+
+    /// The direction faced after turning right.
+    pub fn right(&self) -> Self {
+        self.left().reverse()
+    }
 }
 
 /// Represents the possible actions an agent can take in the Wumpus World.
 ///
-/// ## Variants
-/// * `Move(Direction)` - Move the agent in the specified direction
-/// * `Shoot(Direction)` - Shoot an arrow in the specified direction
-/// * `Climb` - Climb out of the cave
-/// * `Grab` - Grab something on the current position
-#[derive(PartialEq)]
+/// `Forward`, `TurnLeft`, `TurnRight` and `ShootForward` belong to
+/// the AIMA ruleset, where the agent faces a direction instead of
+/// choosing one on each action.
+#[derive(PartialEq, Eq, Hash, Clone, Debug)]
 pub enum Action {
+    /// Move the agent in the specified direction
     Move(Direction),
+    /// Shoot an arrow in the specified direction
     Shoot(Direction),
+    /// Climb out of the cave
     Climb,
+    /// Grab something on the current position
     Grab,
+    /// Move the agent in the direction it faces
+    Forward,
+    /// Turn the agent 90 degrees to the left
+    TurnLeft,
+    /// Turn the agent 90 degrees to the right
+    TurnRight,
+    /// Shoot an arrow in the direction it faces
+    ShootForward,
+}
+
+/// The AIMA percept vector, as perceived after an action.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct Percept {
+    /// The Wumpus is in a neighboring room
+    pub stench: bool,
+    /// A pit is in a neighboring room
+    pub breeze: bool,
+    /// Gold is in the current room
+    pub glitter: bool,
+    /// The last move ran into a wall
+    pub bump: bool,
+    /// The last arrow killed the Wumpus
+    pub scream: bool,
 }
 
-#[derive(Debug)]
+impl Percept {
+    /// The percept in the textbook's order: `[Stench, Breeze,
+    /// Glitter, Bump, Scream]`.
+    pub fn to_array(self) -> [bool; 5] {
+        [
+            self.stench,
+            self.breeze,
+            self.glitter,
+            self.bump,
+            self.scream,
+        ]
+    }
+}
+
+impl Display for Percept {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let names = ["Stench", "Breeze", "Glitter", "Bump", "Scream"];
+        let elements = self
+            .to_array()
+            .iter()
+            .zip(names)
+            .map(|(on, name)| match on {
+                true => name,
+                false => "None",
+            })
+            .collect::<Vec<_>>()
+            .join(", ");
+        f.write_fmt(format_args!("[{}]", elements))
+    }
+}
+
+/// What the agent perceives of the world after each step.
+#[derive(Debug, Clone)]
 pub struct Observation {
     position: Pos,
     directions: HashSet<Direction>,
     senses: HashSet<Sense>,
+    /// Direction the agent faces, under the AIMA ruleset only
+    orientation: Option<Direction>,
 }
 
 impl Observation {
+    /// An observation at `position`, with no sense nor direction
+    /// to move towards.
     pub fn new(position: Pos) -> Self {
         Self {
             position,
             directions: HashSet::new(),
             senses: HashSet::new(),
+            orientation: None,
         }
     }
 
+    /// Direction the agent faces, under the AIMA ruleset only.
+    pub fn orientation(&self) -> Option<&Direction> {
+        self.orientation.as_ref()
+    }
+
+    /// Sets the direction the agent faces, `None` outside of the
+    /// AIMA ruleset.
+    pub fn set_orientation(&mut self, orientation: Option<Direction>) {
+        self.orientation = orientation
+    }
+
+    /// Returns the senses as an AIMA percept vector.
+    pub fn percept(&self) -> Percept {
+        Percept {
+            stench: self.senses.contains(&Sense::Stench),
+            breeze: self.senses.contains(&Sense::Breeze),
+            glitter: self.senses.contains(&Sense::Glitter),
+            bump: self.senses.iter().any(|s| matches!(s, Sense::Bump(_))),
+            scream: self.senses.contains(&Sense::Scream),
+        }
+    }
+
+    /// Position of the agent.
     pub fn position(&self) -> &Pos {
         &self.position
     }
 
+    /// Moves the observed position to `pos`.
     pub fn set_position(&mut self, pos: Pos) {
         self.position = pos
     }
 
+    /// Directions the agent can move towards without bumping into
+    /// a wall.
     pub fn directions(&self) -> &HashSet<Direction> {
         &self.directions
     }
 
+    /// Mutable access to the directions the agent can move
+    /// towards.
     pub fn mut_directions(&mut self) -> &mut HashSet<Direction> {
         &mut self.directions
     }
 
+    /// What the agent senses in its room.
     pub fn senses(&self) -> &HashSet<Sense> {
         &self.senses
     }
 
+    /// Mutable access to what the agent senses in its room.
     pub fn mut_senses(&mut self) -> &mut HashSet<Sense> {
         &mut self.senses
     }
 }
 
+/// What an agent believes about a room, from its knowledge.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Belief {
+    /// The agent has been in the room
+    Visited,
+    /// The room is proven free of pits and of the Wumpus
+    Safe,
+    /// The room is proven to hold a pit
+    Pit,
+    /// The room is proven to hold the Wumpus
+    Wumpus,
+    /// Something was perceived next to the room, but the agent
+    /// can neither prove it safe nor hazardous
+    PossibleHazard,
+    /// Nothing is known about the room
+    Unknown,
+}
+
+impl Display for Belief {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            Belief::Visited => "vvvv",
+            Belief::Safe => "_ok_",
+            Belief::Pit => "_P!_",
+            Belief::Wumpus => "_W!_",
+            Belief::PossibleHazard => "_??_",
+            Belief::Unknown => "....",
+        })
+    }
+}
+
+/// Why a direction was ruled out by an agent.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Rejection {
+    /// The position was already explored
+    Visited,
+    /// The position is proven to hold a pit or the Wumpus
+    Unsafe,
+    /// The position could hold a pit and/or the Wumpus
+    UnknownRisk {
+        /// Whether the position could hold a pit
+        pit: bool,
+        /// Whether the position could hold the Wumpus
+        wumpus: bool,
+    },
+}
+
+impl Display for Rejection {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Rejection::Visited => f.write_str("already visited"),
+            Rejection::Unsafe => f.write_str("proven unsafe"),
+            Rejection::UnknownRisk { pit, wumpus } => match (pit, wumpus) {
+                (true, true) => f.write_str("unknown pit and Wumpus risk"),
+                (true, false) => f.write_str("unknown pit risk"),
+                (false, true) => f.write_str("unknown Wumpus risk"),
+                (false, false) => f.write_str("unknown risk"),
+            },
+        }
+    }
+}
+
+/// A single reason backing a `Decision`.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Reason {
+    /// `fact` was proven from `premises`, which are empty when
+    /// `fact` is known as is
+    Proven {
+        /// The statement proven
+        fact: Statement,
+        /// The statements it was proven from
+        premises: Vec<Statement>,
+    },
+    /// Moving towards `direction` was ruled out
+    Rejected {
+        /// The direction ruled out
+        direction: Direction,
+        /// Why it was ruled out
+        cause: Rejection,
+    },
+    /// Going back the way the agent came, `depth` frames deep
+    /// into its exploration
+    Backtracking {
+        /// Number of frames on the exploration stack
+        depth: usize,
+    },
+    /// Something glitters in the current position
+    Glitter,
+    /// Every treasure was found
+    Satisfied,
+    /// No safe position is left to explore
+    Exhausted,
+}
+
+impl Display for Reason {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Reason::Proven { fact, premises } => match premises.is_empty() {
+                true => f.write_fmt(format_args!("{} known", fact)),
+                false => f.write_fmt(format_args!(
+                    "{} proven from {}",
+                    fact,
+                    premises
+                        .iter()
+                        .map(|p| p.to_string())
+                        .collect::<Vec<_>>()
+                        .join(" & ")
+                )),
+            },
+            Reason::Rejected { direction, cause } => {
+                f.write_fmt(format_args!("{:?} rejected: {}", direction, cause))
+            }
+            Reason::Backtracking { depth } => {
+                f.write_fmt(format_args!("backtracking frame depth {}", depth))
+            }
+            Reason::Glitter => f.write_str("glitter perceived"),
+            Reason::Satisfied => f.write_str("all treasures found"),
+            Reason::Exhausted => f.write_str("no safe position left to explore"),
+        }
+    }
+}
+
+/// An action chosen by an agent, along with the reasons that
+/// led to it.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Decision {
+    action: Action,
+    reasons: Vec<Reason>,
+}
+
+impl Decision {
+    /// A decision to take `action` because of `reasons`.
+    pub fn new(action: Action, reasons: Vec<Reason>) -> Self {
+        Self { action, reasons }
+    }
+
+    /// The action decided on.
+    pub fn action(&self) -> &Action {
+        &self.action
+    }
+
+    /// The reasons that led to the action, in the order they were
+    /// found.
+    pub fn reasons(&self) -> &[Reason] {
+        &self.reasons
+    }
+}
+
+impl Display for Decision {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match &self.action {
+            Action::Move(dir) => f.write_fmt(format_args!("moved {:?}", dir))?,
+            Action::Shoot(dir) => f.write_fmt(format_args!("shot {:?}", dir))?,
+            Action::Climb => f.write_str("climbed out")?,
+            Action::Grab => f.write_str("grabbed")?,
+            Action::Forward => f.write_str("moved forward")?,
+            Action::TurnLeft => f.write_str("turned left")?,
+            Action::TurnRight => f.write_str("turned right")?,
+            Action::ShootForward => f.write_str("shot forward")?,
+        }
+        if !self.reasons().is_empty() {
+            f.write_str(": ")?;
+            f.write_str(
+                &self
+                    .reasons()
+                    .iter()
+                    .map(|r| r.to_string())
+                    .collect::<Vec<_>>()
+                    .join("; "),
+            )?;
+        }
+        Ok(())
+    }
+}
+
 /// Trait for implementing an agent in the Wumpus World.
 ///
 /// Types implementing this trait define the behavior of an agent
 /// by determining what action to take based on the current observation.
-///
-/// ## Methods
-/// * `act` - Determines the next action the agent should take given
-/// the current observation
 pub trait Agent {
-    fn act(&mut self, obs: &Observation) -> Action;
+    /// Determines the next action the agent should take given the
+    /// current observation.
+    fn act(&mut self, obs: &Observation) -> Result<Action, Error>;
+
+    /// Called with the first observation of an episode, before
+    /// any call to `act`.
+    fn on_episode_start(&mut self, _obs: &Observation) {}
+
+    /// Called with the result of the last action taken and the
+    /// reward (score delta) it earned.
+    fn on_result(&mut self, _action: &Action, _result: &ActionResult, _reward: isize) {}
+
+    /// Called with the final score once the episode is over.
+    fn on_episode_end(&mut self, _score: isize) {}
+
+    /// Gets the agent ready to play another episode on `env`, for
+    /// agents costly to build, returning whether it could. Agents
+    /// that can't are built afresh for each episode instead.
+    fn reuse_for(&mut self, _env: &Environment) -> bool {
+        false
+    }
+
+    /// Returns the last decision taken along with its reasons,
+    /// for agents able to explain themselves.
+    fn explain(&self) -> Option<&Decision> {
+        None
+    }
+
+    /// Returns what the agent believes about each room it has
+    /// reasoned about, for agents keeping such knowledge.
+    fn beliefs(&mut self) -> Option<HashMap<Pos, Belief>> {
+        None
+    }
+
+    /// Counts of the knowledge base queries the agent made, for
+    /// agents reasoning with one.
+    fn kb_stats(&self) -> Option<KbStats> {
+        None
+    }
 }
@@ -1,10 +1,189 @@
-use std::collections::HashSet;
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::fmt;
+use std::sync::Arc;
+use std::time::Duration;
 
-use crate::agent::{Action, Agent, Direction, Observation};
-use crate::env::Sense;
-use crate::grid::Pos;
-use crate::kb::KnowledgeBase;
+use rand::seq::IteratorRandom;
+use rand::{Rng, SeedableRng, rngs::StdRng};
+
+use crate::agent::{Action, Agent, Belief, Decision, Direction, Observation, Reason, Rejection};
+use crate::env::{ActionResult, Environment, Ruleset, Sense, Snapshot};
+use crate::error::Error;
+use crate::external::ExternalAgent;
+use crate::grid::{Grid, Pos};
+use crate::kb::{KbStats, KnowledgeBase};
 use crate::logic::Statement;
+use crate::room::RoomKind;
+use crate::scoring::ScoringRules;
+
+const ALL_DIRECTIONS: [Direction; 4] = [
+    Direction::North,
+    Direction::South,
+    Direction::East,
+    Direction::West,
+];
+
+/// The agents available to the game, used to build a fresh
+/// agent for a given environment.
+#[derive(Clone, Debug, PartialEq)]
+pub enum AgentKind {
+    /// Knowledge-based agent, told the grid dimensions
+    KnowledgeBased,
+    /// Knowledge-based agent that isn't told the grid dimensions
+    KnowledgeBasedUnknownSize,
+    /// Monte Carlo agent sampling worlds consistent with what it
+    /// perceived
+    MonteCarlo {
+        /// Simulations run per step
+        iterations: usize,
+    },
+    /// Agent played by another program, started with `args` and
+    /// given `timeout` to reply
+    External {
+        /// The program to start
+        program: String,
+        /// The arguments it is started with
+        args: Vec<String>,
+        /// Time the program is given to reply
+        timeout: Duration,
+    },
+}
+
+impl AgentKind {
+    /// Every agent the game ships with, at its default settings,
+    /// as pitted against each other in the arena.
+    pub fn registered() -> Vec<AgentKind> {
+        vec![
+            AgentKind::KnowledgeBased,
+            AgentKind::KnowledgeBasedUnknownSize,
+            AgentKind::MonteCarlo {
+                iterations: DEFAULT_MCTS_ITERATIONS,
+            },
+        ]
+    }
+
+    /// Short name of the agent, as given on the command line.
+    pub fn name(&self) -> &'static str {
+        match self {
+            AgentKind::KnowledgeBased => "kb",
+            AgentKind::KnowledgeBasedUnknownSize => "kb-unknown",
+            AgentKind::MonteCarlo { .. } => "mcts",
+            AgentKind::External { .. } => "external",
+        }
+    }
+
+    /// Name the agent is reported as. External agents are named
+    /// after the command starting them, with the program's file
+    /// name, so that several of them can be told apart.
+    pub fn label(&self) -> String {
+        match self {
+            AgentKind::External { program, args, .. } => {
+                let file_name = std::path::Path::new(program)
+                    .file_name()
+                    .map_or(program.clone(), |name| name.to_string_lossy().into_owned());
+                std::iter::once(file_name)
+                    .chain(args.iter().cloned())
+                    .collect::<Vec<_>>()
+                    .join(" ")
+            }
+            _ => self.name().to_string(),
+        }
+    }
+
+    /// Whether the agent can play by `ruleset`. Only external
+    /// agents can turn and move forward, the agents the game
+    /// ships with play by absolute directions.
+    pub fn supports(&self, ruleset: Ruleset) -> bool {
+        match self {
+            AgentKind::External { .. } => true,
+            _ => ruleset == Ruleset::Absolute,
+        }
+    }
+
+    /// Builds a fresh agent to play `env`. Agents drawing at
+    /// random are seeded with the seed of the world, if any, so
+    /// that runs on generated worlds can be reproduced. Fails when
+    /// the agent can't play by its ruleset.
+    pub fn build(&self, env: &Environment) -> Result<Box<dyn Agent>, Error> {
+        if !self.supports(env.ruleset()) {
+            return Err(Error::UnsupportedRuleset {
+                agent: self.name(),
+                ruleset: env.ruleset(),
+            });
+        }
+        let start_pos = env.start_position();
+        let (nrows, ncols) = (env.grid().nrows(), env.grid().ncols());
+        Ok(match self {
+            AgentKind::KnowledgeBased => {
+                Box::new(KnowledgeBasedAgent::new(start_pos, nrows, ncols)?)
+            }
+            AgentKind::KnowledgeBasedUnknownSize => {
+                Box::new(KnowledgeBasedAgent::with_unknown_size(start_pos))
+            }
+            AgentKind::MonteCarlo { iterations } => {
+                let agent = MonteCarloAgent::new(start_pos, nrows, ncols, *iterations)?
+                    .with_scoring(env.scoring().clone());
+                Box::new(match env.seed() {
+                    Some(seed) => agent.with_seed(seed),
+                    None => agent,
+                })
+            }
+            AgentKind::External {
+                program,
+                args,
+                timeout,
+            } => Box::new(ExternalAgent::spawn(program, args, *timeout, nrows, ncols)?),
+        })
+    }
+}
+
+/// Builds a fresh agent to play the environment it is given.
+type BuildAgent = dyn Fn(&Environment) -> Result<Box<dyn Agent>, Error> + Send + Sync;
+
+/// A named way of building agents, for benchmarks and arenas to
+/// run agents the game doesn't ship with alongside its own.
+#[derive(Clone)]
+pub struct Contender {
+    name: String,
+    build: Arc<BuildAgent>,
+}
+
+impl Contender {
+    /// The agents `build` makes, reported as `name`.
+    pub fn new(
+        name: impl Into<String>,
+        build: impl Fn(&Environment) -> Result<Box<dyn Agent>, Error> + Send + Sync + 'static,
+    ) -> Self {
+        Self {
+            name: name.into(),
+            build: Arc::new(build),
+        }
+    }
+
+    /// Name the agent is reported as.
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// Builds a fresh agent to play `env`.
+    pub fn build(&self, env: &Environment) -> Result<Box<dyn Agent>, Error> {
+        (self.build)(env)
+    }
+}
+
+impl From<AgentKind> for Contender {
+    fn from(kind: AgentKind) -> Self {
+        Self::new(kind.label(), move |env| kind.build(env))
+    }
+}
+
+impl fmt::Debug for Contender {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Contender")
+            .field("name", &self.name)
+            .finish()
+    }
+}
 
 struct Stack<T> {
     vec: Vec<T>,
@@ -44,26 +223,74 @@ impl<T> Stack<T> {
     pub fn clear(&mut self) {
         self.vec.clear();
     }
+
+    pub fn len(&self) -> usize {
+        self.vec.len()
+    }
 }
 
 type Frame<T> = Stack<T>;
 
+/// A move the agent committed to, waiting for the environment to
+/// confirm it before updating its position.
+enum PendingMove {
+    /// Exploring a new position, a frame was pushed for it
+    Forward(Direction),
+    /// Backtracking to the previous position
+    Backward(Direction),
+}
+
 // Knowledge-Based Agent Impl //////////////////////////////
+/// Agent exploring the cave one proven-safe room at a time, by
+/// reasoning over its percepts with a `KnowledgeBase`.
 pub struct KnowledgeBasedAgent {
     kb: KnowledgeBase,
     start_pos: Pos,
     curr_pos: Pos,
-    grid_rows: usize,
-    grid_cols: usize,
+    /// Grid dimensions, unknown until bumping into a wall when
+    /// the agent is not told them
+    grid_rows: Option<usize>,
+    grid_cols: Option<usize>,
     stack: Stack<Frame<Direction>>,
     visited: HashSet<Pos>,
+    axiomatized: HashSet<Pos>,
+    /// Disjunction stating there's a Wumpus somewhere, grown as
+    /// rooms are discovered when the grid dimensions are unknown
+    wumpus_axiom: Option<Statement>,
     satisfied: bool,
     total_treasures: usize,
     treasures: usize,
+    pending: Option<PendingMove>,
+    last_decision: Option<Decision>,
 }
 
 impl KnowledgeBasedAgent {
-    pub fn new(start_pos: &Pos, grid_rows: usize, grid_cols: usize) -> Self {
+    /// Creates an agent told the grid dimensions. Fails when
+    /// `start_pos` lies outside the grid.
+    pub fn new(start_pos: &Pos, grid_rows: usize, grid_cols: usize) -> Result<Self, Error> {
+        if start_pos.row >= grid_rows || start_pos.col >= grid_cols {
+            return Err(Error::OffGrid(start_pos.clone()));
+        }
+        Ok(Self::with_dimensions(
+            start_pos,
+            Some(grid_rows),
+            Some(grid_cols),
+        ))
+    }
+
+    /// Creates an agent that isn't told the grid dimensions, as in
+    /// the classic problem. It discovers the south and east walls
+    /// by bumping into them, and axiomatizes rooms lazily as they
+    /// become known instead of upfront for the whole grid.
+    pub fn with_unknown_size(start_pos: &Pos) -> Self {
+        Self::with_dimensions(start_pos, None, None)
+    }
+
+    fn with_dimensions(
+        start_pos: &Pos,
+        grid_rows: Option<usize>,
+        grid_cols: Option<usize>,
+    ) -> Self {
         let mut agent = Self {
             kb: KnowledgeBase::new(),
             start_pos: start_pos.clone(),
@@ -72,101 +299,156 @@ impl KnowledgeBasedAgent {
             grid_cols,
             stack: Stack::new(),
             visited: HashSet::new(),
+            axiomatized: HashSet::new(),
+            wumpus_axiom: None,
             satisfied: false,
             total_treasures: 1,
             treasures: 0,
+            pending: None,
+            last_decision: None,
         };
         agent.reset();
 
         agent
     }
 
+    /// Transforms the senses of `obs` into a statement. Bumps and
+    /// refused climbs say nothing of the room, and are left out.
     pub fn make_percept_stmt(&self, obs: &Observation) -> Statement {
         let pos = obs.position();
         let mut senses = obs.senses().clone();
+        senses.retain(|sense| !matches!(sense, Sense::Bump(_) | Sense::Ceil));
 
-        if senses.len() == 0 {
+        if senses.is_empty() {
             /* current cell contains no sense */
             return make_void_atomic(pos);
         }
 
-        let mut conjuncts: Vec<Statement> = Vec::new();
-        if senses.contains(&Sense::Stench) {
-            senses.remove(&Sense::Stench);
-            conjuncts.push(make_stench_atomic(pos));
-        } else {
-            conjuncts.push(make_stench_atomic(pos).negate());
-        }
-
-        if senses.contains(&Sense::Breeze) {
-            senses.remove(&Sense::Breeze);
-            conjuncts.push(make_breeze_atomic(pos));
-        } else {
-            conjuncts.push(make_breeze_atomic(pos).negate());
-        }
+        let stench = match senses.remove(&Sense::Stench) {
+            true => make_stench_atomic(pos),
+            false => make_stench_atomic(pos).negate(),
+        };
+        let breeze = match senses.remove(&Sense::Breeze) {
+            true => make_breeze_atomic(pos),
+            false => make_breeze_atomic(pos).negate(),
+        };
 
-        for sense in senses {
-            conjuncts.push(match sense {
-                Sense::Scream(dir) => {
-                    let wumpus_pos = pos + &dir;
-                    Statement::Atomic(format!("Sc_{},{}", wumpus_pos.row, wumpus_pos.col))
-                }
-                Sense::Glitter => Statement::Atomic(format!("Gl_{},{}", pos.row, pos.col)),
-                _ => panic!(),
-            });
-        }
+        let mut conjuncts = vec![breeze];
+        conjuncts.extend(senses.into_iter().map(|sense| match sense {
+            Sense::Scream => Statement::Atomic("Sc".to_string()),
+            Sense::Glitter => Statement::Atomic(format!("Gl_{},{}", pos.row, pos.col)),
+            _ => unreachable!(),
+        }));
 
-        make_conjuncts(&conjuncts).unwrap()
+        make_conjuncts(stench, conjuncts)
     }
 
     /* pub fn make_action_stmt(&self, action: &Action) -> Statement {
         todo!()
     } */
 
+    /// Whether moving from `pos` towards `direction` stays inside
+    /// the grid, as far as the agent knows.
     fn is_direction_valid(&self, pos: &Pos, direction: &Direction) -> bool {
         match direction {
             Direction::North => pos.row > 0,
-            Direction::South => pos.row < self.grid_rows - 1,
-            Direction::East => pos.col < self.grid_cols - 1,
+            Direction::South => self.grid_rows.is_none_or(|nrows| pos.row < nrows - 1),
+            Direction::East => self.grid_cols.is_none_or(|ncols| pos.col < ncols - 1),
             Direction::West => pos.col > 0,
         }
     }
 
-    fn neighborhood(&self, pos: &Pos) -> HashSet<Pos> {
-        let mut dirs = HashSet::new();
-        dirs.insert(Direction::North);
-        dirs.insert(Direction::South);
-        dirs.insert(Direction::East);
-        dirs.insert(Direction::West);
+    fn valid_directions(&self, pos: &Pos) -> HashSet<Direction> {
+        ALL_DIRECTIONS
+            .iter()
+            .filter(|dir| self.is_direction_valid(pos, dir))
+            .cloned()
+            .collect()
+    }
 
-        dirs.retain(|dir| self.is_direction_valid(pos, dir));
+    /// Returns the position next to `pos` towards `direction`, or
+    /// `None` when a known wall is in the way.
+    fn neighbor(&self, pos: &Pos, direction: &Direction) -> Option<Pos> {
+        match self.is_direction_valid(pos, direction) {
+            true => pos.try_add(direction).ok(),
+            false => None,
+        }
+    }
 
-        dirs.iter().map(|dir| pos + dir).collect()
+    fn neighborhood(&self, pos: &Pos) -> HashSet<Pos> {
+        self.valid_directions(pos)
+            .iter()
+            .filter_map(|dir| self.neighbor(pos, dir))
+            .collect()
     }
 
-    fn retain_non_visited(&self, directions: &mut HashSet<Direction>) {
-        directions.retain(|dir| !self.visited.contains(&(&self.curr_pos + dir)));
+    fn is_visited_towards(&self, direction: &Direction) -> bool {
+        self.neighbor(&self.curr_pos, direction)
+            .is_some_and(|pos| self.visited.contains(&pos))
+    }
+
+    fn retain_non_visited(&self, directions: &mut HashSet<Direction>, reasons: &mut Vec<Reason>) {
+        for dir in ALL_DIRECTIONS.iter().filter(|dir| directions.contains(dir)) {
+            if self.is_visited_towards(dir) {
+                reasons.push(Reason::Rejected {
+                    direction: dir.clone(),
+                    cause: Rejection::Visited,
+                });
+            }
+        }
+        directions.retain(|dir| !self.is_visited_towards(dir));
     }
 
     /// The agent part that actually uses reasoning to eliminate
     /// non-safe directions according to its logic. Given a set
     /// of directions, it retains safe ones by asking the `KB`
-    /// for their safety.
-    fn retain_safe(&mut self, directions: &mut HashSet<Direction>) {
-        let positions: HashSet<(Direction, Pos)> = directions
+    /// for their safety, and records why the others were rejected.
+    fn retain_safe(&mut self, directions: &mut HashSet<Direction>, reasons: &mut Vec<Reason>) {
+        let positions: Vec<(Direction, Pos)> = ALL_DIRECTIONS
             .iter()
-            .cloned()
-            .map(|dir| (dir.clone(), &self.curr_pos + &dir))
+            .filter(|dir| directions.contains(dir))
+            .filter_map(|dir| Some((dir.clone(), self.neighbor(&self.curr_pos, dir)?)))
             .collect();
 
         for (dir, pos) in positions {
             let answer = self.kb.ask(&make_safe_atomic(&pos));
             if answer.is_none() || !answer.unwrap() {
                 directions.remove(&dir);
+                reasons.push(Reason::Rejected {
+                    direction: dir,
+                    cause: self.rejection(&pos),
+                });
             }
         }
     }
 
+    /// Tells apart positions proven to be hazardous from the ones
+    /// the `KB` simply cannot prove safe. The queries it takes
+    /// aren't counted in the `KB` stats.
+    fn rejection(&mut self, pos: &Pos) -> Rejection {
+        let pit = make_pit_atomic(pos);
+        let wumpus = make_wumpus_atomic(pos);
+        if self.kb.ask_uncounted(&pit) == Some(true) || self.kb.ask_uncounted(&wumpus) == Some(true)
+        {
+            return Rejection::Unsafe;
+        }
+
+        Rejection::UnknownRisk {
+            pit: self.kb.ask_uncounted(&pit.negate()) != Some(true),
+            wumpus: self.kb.ask_uncounted(&wumpus.negate()) != Some(true),
+        }
+    }
+
+    /// Explains why the position towards `direction` is safe,
+    /// along with the statements the `KB` proved it from.
+    fn safety_proof(&mut self, direction: &Direction) -> Result<Reason, Error> {
+        let fact = make_safe_atomic(&self.curr_pos.try_add(direction)?);
+        self.kb.ask_uncounted(&fact);
+        let premises = self.kb.premises(&fact).unwrap_or_default().to_vec();
+
+        Ok(Reason::Proven { fact, premises })
+    }
+
     /// Updates the agent's current position by moving it in the
     /// given direction and marks the new position as visited.
     ///
@@ -179,11 +461,20 @@ impl KnowledgeBasedAgent {
     /// * Updates `self.curr_pos` to the new position
     /// * Inserts the new position into `self.visited` set
     fn update_and_mark_position(&mut self, direction: &Direction) {
-        self.curr_pos += direction;
-        self.visited.insert(self.curr_pos.clone());
+        // The environment confirmed the move, so it stays on the grid
+        let next = self.curr_pos.try_add(direction);
+        debug_assert!(next.is_ok(), "confirmed move left the grid");
+        if let Ok(next) = next {
+            self.curr_pos = next;
+            self.visited.insert(self.curr_pos.clone());
+        }
     }
 
-    pub fn ask_for_action(&mut self, obs: &Observation) -> Action {
+    /// Chooses the next action, along with the reasons that led
+    /// to it: the proof of safety of the room moved into, why the
+    /// other directions were rejected, and whether the agent is
+    /// backtracking.
+    pub fn ask_for_action(&mut self, obs: &Observation) -> Result<Decision, Error> {
         /* Action strategy algorithm */
 
         // ---------------------------------------
@@ -191,8 +482,7 @@ impl KnowledgeBasedAgent {
         // ---------------------------------------
 
         if obs.senses().contains(&Sense::Glitter) {
-            self.treasures += 1;
-            return Action::Grab;
+            return Ok(Decision::new(Action::Grab, vec![Reason::Glitter]));
         }
 
         if self.treasures == self.total_treasures {
@@ -200,199 +490,322 @@ impl KnowledgeBasedAgent {
         }
 
         if self.satisfied && self.curr_pos == self.start_pos {
-            return Action::Climb;
+            return Ok(Decision::new(Action::Climb, vec![Reason::Satisfied]));
         }
 
         let frame = self.stack.peek();
         if frame.is_empty() {
             self.stack.pop();
-            let mut directions = obs.directions().clone();
-            self.retain_non_visited(&mut directions);
-            self.retain_safe(&mut directions);
+            let mut reasons = Vec::new();
+            let mut directions = self.valid_directions(&self.curr_pos);
+            self.retain_non_visited(&mut directions, &mut reasons);
+            self.retain_safe(&mut directions, &mut reasons);
             if directions.is_empty() {
                 if self.stack.is_empty() {
                     // There's no way to backtrack, so climb out!
-                    return Action::Climb;
+                    reasons.push(Reason::Exhausted);
+                    return Ok(Decision::new(Action::Climb, reasons));
                 }
+                let depth = self.stack.len();
                 let frame = self.stack.mut_peek();
                 let dir = frame.pop();
                 let rev = dir.reverse();
-                self.update_and_mark_position(&rev);
-                return Action::Move(rev);
+                self.pending = Some(PendingMove::Backward(rev.clone()));
+                reasons.push(Reason::Backtracking { depth });
+                Ok(Decision::new(Action::Move(rev), reasons))
             } else {
-                let frame = Frame::of(directions.into_iter());
+                // Directions are pushed in a fixed order so that the
+                // agent behaves the same way on the same world
+                let frame = Frame::of(
+                    ALL_DIRECTIONS
+                        .iter()
+                        .filter(|dir| directions.contains(dir))
+                        .cloned(),
+                );
                 let dir = frame.peek().clone();
                 self.stack.push(frame);
                 self.stack.push(Frame::new());
-                self.update_and_mark_position(&dir);
-                return Action::Move(dir);
+                self.pending = Some(PendingMove::Forward(dir.clone()));
+                reasons.insert(0, self.safety_proof(&dir)?);
+                Ok(Decision::new(Action::Move(dir), reasons))
             }
         } else {
             let dir = frame.peek().clone();
             self.stack.push(Frame::new());
-            self.update_and_mark_position(&dir);
-            return Action::Move(dir);
+            self.pending = Some(PendingMove::Forward(dir.clone()));
+            let proof = self.safety_proof(&dir)?;
+            Ok(Decision::new(Action::Move(dir), vec![proof]))
         }
     }
 
-    fn axiomatize(&mut self) {
-        // There's only one Wumpus in the grid
-        let all = (0..self.grid_rows)
-            .flat_map(|a| (0..self.grid_cols).map(move |b| (a, b)))
-            .map(|(row, col)| make_wumpus_atomic(&Pos::new(row, col)));
-
-        let disjuncts: Vec<Statement> = all.clone().collect();
-        self.kb.tell(make_disjuncts(&disjuncts).unwrap());
-
-        for i in 0..self.grid_rows {
-            for j in 0..self.grid_cols {
-                let cause = make_wumpus_atomic(&Pos::new(i, j));
-                let conjuncts: Vec<Statement> = all
-                    .clone()
-                    .map(|s| Statement::NotClause(s.boxed()))
-                    .collect();
-
-                self.kb.tell(Statement::ImplyClause(
-                    cause.boxed(),
-                    make_conjuncts(&conjuncts).unwrap().boxed(),
-                ));
-            }
+    /// Returns what the agent believes about each room it knows
+    /// of, derived from its `KB`. When the grid dimensions are
+    /// unknown, only rooms discovered so far are included.
+    pub fn beliefs(&mut self) -> HashMap<Pos, Belief> {
+        self.all_positions()
+            .into_iter()
+            .map(|pos| {
+                let belief = self.belief_at(&pos);
+                (pos, belief)
+            })
+            .collect()
+    }
+
+    fn belief_at(&mut self, pos: &Pos) -> Belief {
+        if self.visited.contains(pos) {
+            return Belief::Visited;
+        }
+        if self.kb.ask_uncounted(&make_safe_atomic(pos)) == Some(true) {
+            return Belief::Safe;
+        }
+        if self.kb.ask_uncounted(&make_pit_atomic(pos)) == Some(true) {
+            return Belief::Pit;
+        }
+        if self.kb.ask_uncounted(&make_wumpus_atomic(pos)) == Some(true) {
+            return Belief::Wumpus;
         }
 
-        // A safe place is one which contains no Pit and no Wumpus
-        // S_r,c <=> (~W_r,c & ~P_r,c)
-        let mut conjuncts: Vec<Statement> = Vec::new();
-        for i in 0..self.grid_rows {
-            for j in 0..self.grid_cols {
-                conjuncts.push(Statement::EquivalClause(
-                    make_safe_atomic(&Pos::new(i, j)).boxed(),
-                    Statement::AndClause(
-                        Statement::NotClause(make_wumpus_atomic(&Pos::new(i, j)).boxed()).boxed(),
-                        Statement::NotClause(make_pit_atomic(&Pos::new(i, j)).boxed()).boxed(),
-                    )
-                    .boxed(),
-                ));
-            }
+        match self
+            .neighborhood(pos)
+            .iter()
+            .any(|n| self.visited.contains(n))
+        {
+            true => Belief::PossibleHazard,
+            false => Belief::Unknown,
         }
-        self.kb.tell(make_conjuncts(&conjuncts).unwrap());
+    }
 
+    fn all_positions(&self) -> Vec<Pos> {
+        let (Some(nrows), Some(ncols)) = (self.grid_rows, self.grid_cols) else {
+            let mut positions: Vec<Pos> = self
+                .axiomatized
+                .iter()
+                .filter(|pos| self.within_known_walls(pos))
+                .cloned()
+                .collect();
+            positions.sort_by_key(|pos| (pos.row, pos.col));
+            return positions;
+        };
+        (0..nrows)
+            .flat_map(|a| (0..ncols).map(move |b| Pos::new(a, b)))
+            .collect()
+    }
+
+    fn axiomatize(&mut self) {
         // Start position is safe
         self.kb.tell(make_safe_atomic(&self.start_pos));
 
-        // A void position is safe and all neighboring positions
-        for i in 0..self.grid_rows {
-            for j in 0..self.grid_cols {
-                let void_pos = Pos::new(i, j);
-                let void_stmt = make_void_atomic(&void_pos);
-                let mut conjuncts: Vec<Statement> = Vec::new();
-                conjuncts.push(make_safe_atomic(&void_pos));
-                conjuncts.extend(
-                    self.neighborhood(&void_pos)
-                        .iter()
-                        .map(|np| make_safe_atomic(&np)),
-                );
-                self.kb.tell(Statement::EquivalClause(
-                    void_stmt.boxed(),
-                    make_conjuncts(&conjuncts).unwrap().boxed(),
-                ));
-            }
+        if self.grid_rows.is_none() || self.grid_cols.is_none() {
+            // Rooms are axiomatized lazily as they become known
+            self.tell_wumpus_axiom();
+            return;
         }
 
+        for pos in self.all_positions() {
+            self.axiomatize_room(&pos);
+        }
+        self.tell_wumpus_axiom();
+    }
+
+    /// Tells the `KB` there's a Wumpus in one of the rooms known
+    /// so far, replacing the previous such axiom. While some walls
+    /// are unknown, the Wumpus may also lie in a room not yet
+    /// discovered, which `W_?` stands for.
+    fn tell_wumpus_axiom(&mut self) {
+        let positions = self.all_positions();
+        let known_walls = self.grid_rows.is_some() && self.grid_cols.is_some();
+        let axiom = match positions.split_first() {
+            Some((first, rest)) if known_walls => make_disjuncts(
+                make_wumpus_atomic(first),
+                rest.iter().map(make_wumpus_atomic).collect(),
+            ),
+            _ => make_disjuncts(
+                Statement::Atomic("W_?".to_string()),
+                positions.iter().map(make_wumpus_atomic).collect(),
+            ),
+        };
+
+        if let Some(previous) = self.wumpus_axiom.take() {
+            self.kb.retract(&previous);
+        }
+        self.kb.tell(axiom.clone());
+        self.wumpus_axiom = Some(axiom);
+    }
+
+    /// Whether `pos` lies within the walls discovered so far.
+    fn within_known_walls(&self, pos: &Pos) -> bool {
+        self.grid_rows.is_none_or(|nrows| pos.row < nrows)
+            && self.grid_cols.is_none_or(|ncols| pos.col < ncols)
+    }
+
+    /// Tells the `KB` every axiom about the room at `pos`, given
+    /// what the agent currently knows about its neighborhood.
+    fn axiomatize_room(&mut self, pos: &Pos) {
+        if !self.axiomatized.insert(pos.clone()) {
+            return;
+        }
+        let neighborhood = self.neighborhood(pos);
+
+        // There's only one Wumpus in the grid, so a Wumpus here
+        // rules out a Wumpus in every other room, and vice versa
+        for other in self.axiomatized.iter().filter(|other| *other != pos) {
+            self.kb.tell(Statement::ImplyClause(
+                make_wumpus_atomic(pos).boxed(),
+                make_wumpus_atomic(other).negate().boxed(),
+            ));
+            self.kb.tell(Statement::ImplyClause(
+                make_wumpus_atomic(other).boxed(),
+                make_wumpus_atomic(pos).negate().boxed(),
+            ));
+        }
+
+        // A safe place is one which contains no Pit and no Wumpus
+        // S_r,c <=> (~W_r,c & ~P_r,c)
+        self.kb.tell(Statement::EquivalClause(
+            make_safe_atomic(pos).boxed(),
+            Statement::AndClause(
+                make_wumpus_atomic(pos).negate().boxed(),
+                make_pit_atomic(pos).negate().boxed(),
+            )
+            .boxed(),
+        ));
+
+        // A void position is safe and all neighboring positions
+        self.kb.tell(Statement::EquivalClause(
+            make_void_atomic(pos).boxed(),
+            make_conjuncts(
+                make_safe_atomic(pos),
+                neighborhood.iter().map(make_safe_atomic).collect(),
+            )
+            .boxed(),
+        ));
+
         // No position can contain both Wumpus and Pit, that is,
         // all positions satisfy: ~(W_r,c & P_r,c)
-        let mut conjuncts: Vec<Statement> = Vec::new();
-        for i in 0..self.grid_rows {
-            for j in 0..self.grid_cols {
-                let pos = Pos::new(i, j);
-                conjuncts.push(Statement::NotClause(
-                    Statement::AndClause(
-                        make_wumpus_atomic(&pos).boxed(),
-                        make_pit_atomic(&pos).boxed(),
-                    )
-                    .boxed(),
-                ));
-            }
-        }
-        self.kb.tell(make_conjuncts(&conjuncts).unwrap());
+        self.kb.tell(Statement::NotClause(
+            Statement::AndClause(
+                make_wumpus_atomic(pos).boxed(),
+                make_pit_atomic(pos).boxed(),
+            )
+            .boxed(),
+        ));
+
+        let neighborhood: Vec<Pos> = neighborhood.into_iter().collect();
+        let Some((first, rest)) = neighborhood.split_first() else {
+            return;
+        };
 
         // A position is breezy iff some neighboring
         // position contains a Pit
-        for i in 0..self.grid_rows {
-            for j in 0..self.grid_cols {
-                let pos = Pos::new(i, j);
-                let disjuncts: Vec<Statement> = self
-                    .neighborhood(&pos)
-                    .iter()
-                    .map(|np| make_pit_atomic(&np))
-                    .collect();
-                self.kb.tell(Statement::EquivalClause(
-                    make_breeze_atomic(&pos).boxed(),
-                    make_disjuncts(&disjuncts).unwrap().boxed(),
-                ));
-            }
-        }
+        self.kb.tell(Statement::EquivalClause(
+            make_breeze_atomic(pos).boxed(),
+            make_disjuncts(
+                make_pit_atomic(first),
+                rest.iter().map(make_pit_atomic).collect(),
+            )
+            .boxed(),
+        ));
 
         // A position is stenchy iff some neighboring
         // position contains a Wumpus
-        for i in 0..self.grid_rows {
-            for j in 0..self.grid_cols {
-                let pos = Pos::new(i, j);
-                let disjuncts: Vec<Statement> = self
-                    .neighborhood(&pos)
-                    .iter()
-                    .map(|np| make_wumpus_atomic(&np))
-                    .collect();
-
-                self.kb.tell(Statement::EquivalClause(
-                    make_stench_atomic(&pos).boxed(),
-                    make_disjuncts(&disjuncts).unwrap().boxed(),
-                ));
-            }
-        }
+        self.kb.tell(Statement::EquivalClause(
+            make_stench_atomic(pos).boxed(),
+            make_disjuncts(
+                make_wumpus_atomic(first),
+                rest.iter().map(make_wumpus_atomic).collect(),
+            )
+            .boxed(),
+        ));
 
         // A position containing a Pit implies all neighboring
         // positions to be breezy
-        for i in 0..self.grid_rows {
-            for j in 0..self.grid_cols {
-                let pos = Pos::new(i, j);
-                let conjuncts: Vec<Statement> = self
-                    .neighborhood(&pos)
-                    .iter()
-                    .map(|np| make_breeze_atomic(&np))
-                    .collect();
-
-                self.kb.tell(Statement::ImplyClause(
-                    make_pit_atomic(&pos).boxed(),
-                    make_conjuncts(&conjuncts).unwrap().boxed(),
-                ));
-            }
-        }
+        self.kb.tell(Statement::ImplyClause(
+            make_pit_atomic(pos).boxed(),
+            make_conjuncts(
+                make_breeze_atomic(first),
+                rest.iter().map(make_breeze_atomic).collect(),
+            )
+            .boxed(),
+        ));
 
         // A position containing a Wumpus implies all neighboring
         // positions to be stenchy
-        for i in 0..self.grid_rows {
-            for j in 0..self.grid_cols {
-                let pos = Pos::new(i, j);
-                let conjuncts: Vec<Statement> = self
-                    .neighborhood(&pos)
-                    .iter()
-                    .map(|np| make_stench_atomic(&np))
-                    .collect();
+        self.kb.tell(Statement::ImplyClause(
+            make_wumpus_atomic(pos).boxed(),
+            make_conjuncts(
+                make_stench_atomic(first),
+                rest.iter().map(make_stench_atomic).collect(),
+            )
+            .boxed(),
+        ));
+    }
 
-                self.kb.tell(Statement::ImplyClause(
-                    make_wumpus_atomic(&pos).boxed(),
-                    make_conjuncts(&conjuncts).unwrap().boxed(),
-                ));
+    /// Axiomatizes the rooms the agent gets to know by standing at
+    /// `pos`: the room itself and its neighbors. Only needed when
+    /// the grid dimensions are unknown.
+    fn discover(&mut self, pos: &Pos) {
+        let known = self.axiomatized.len();
+        self.axiomatize_room(pos);
+        for np in self.neighborhood(pos) {
+            self.axiomatize_room(&np);
+        }
+        if self.axiomatized.len() > known {
+            self.tell_wumpus_axiom();
+        }
+    }
+
+    /// Records the wall the agent bumped into when moving from its
+    /// current position towards `direction`. The rooms already
+    /// axiomatized beyond it don't exist, so they can't hold a Pit
+    /// nor a Wumpus, which keeps the axioms told about them
+    /// consistent. Once both walls are known, the remaining rooms
+    /// of the grid are axiomatized as for an agent told its size.
+    fn discover_wall(&mut self, direction: &Direction) {
+        let known = self.grid_rows.is_some() && self.grid_cols.is_some();
+        match direction {
+            Direction::South => self.grid_rows = Some(self.curr_pos.row + 1),
+            Direction::East => self.grid_cols = Some(self.curr_pos.col + 1),
+            // Coordinates never go below zero, so the north and
+            // west walls are already known
+            Direction::North | Direction::West => return,
+        }
+        if known {
+            return;
+        }
+
+        let mut beyond: Vec<Pos> = self
+            .axiomatized
+            .iter()
+            .filter(|pos| !self.within_known_walls(pos))
+            .cloned()
+            .collect();
+        beyond.sort_by_key(|pos| (pos.row, pos.col));
+        for pos in beyond {
+            self.kb.tell(make_safe_atomic(&pos));
+            self.kb.tell(make_pit_atomic(&pos).negate());
+            self.kb.tell(make_wumpus_atomic(&pos).negate());
+        }
+
+        if self.grid_rows.is_some() && self.grid_cols.is_some() {
+            for pos in self.all_positions() {
+                self.axiomatize_room(&pos);
             }
         }
+        self.tell_wumpus_axiom();
     }
 
+    /// Starts the agent over from the start position, forgetting
+    /// everything it learned of the world.
     pub fn reset(&mut self) {
         self.treasures = 0;
         self.satisfied = false;
+        self.pending = None;
+        self.last_decision = None;
         self.kb.clear();
         self.visited.clear();
         self.visited.insert(self.start_pos.clone());
+        self.axiomatized.clear();
+        self.wumpus_axiom = None;
         self.stack.clear();
         self.stack.push(Frame::new());
         self.curr_pos = self.start_pos.clone();
@@ -402,18 +815,679 @@ impl KnowledgeBasedAgent {
 }
 
 impl Agent for KnowledgeBasedAgent {
-    fn act(&mut self, obs: &Observation) -> Action {
+    fn act(&mut self, obs: &Observation) -> Result<Action, Error> {
+        self.discover(obs.position());
+
         // First, transform observation into a statement,
         // and `tell` it to the `KB`
         self.kb.tell(self.make_percept_stmt(obs));
 
         // Then, `ask` the `KB` for an action
-        let action = self.ask_for_action(obs);
+        let decision = self.ask_for_action(obs)?;
+        let action = decision.action().clone();
+        self.last_decision = Some(decision);
 
         // Finally, `tell` the `KB` for the chosen action
         // self.kb.tell(self.make_action_stmt(&action));
+        Ok(action)
+    }
+
+    fn on_episode_start(&mut self, _obs: &Observation) {
+        self.reset();
+    }
+
+    /// Commits the pending move once the environment confirms it.
+    /// On a bump, the frame pushed for the move is dropped along
+    /// with its direction, so the agent tries another one.
+    fn on_result(&mut self, _action: &Action, result: &ActionResult, _reward: isize) {
+        match result {
+            ActionResult::Grabbed(_) => self.treasures += 1,
+            ActionResult::Sense(Sense::Bump(direction)) => {
+                self.discover_wall(direction);
+                if let Some(PendingMove::Forward(_)) = self.pending.take() {
+                    self.stack.pop();
+                    self.stack.mut_peek().pop();
+                }
+            }
+            _ => match self.pending.take() {
+                Some(PendingMove::Forward(dir)) | Some(PendingMove::Backward(dir)) => {
+                    self.update_and_mark_position(&dir)
+                }
+                None => {}
+            },
+        }
+    }
+
+    fn explain(&self) -> Option<&Decision> {
+        self.last_decision.as_ref()
+    }
+
+    fn beliefs(&mut self) -> Option<HashMap<Pos, Belief>> {
+        Some(KnowledgeBasedAgent::beliefs(self))
+    }
+
+    fn kb_stats(&self) -> Option<KbStats> {
+        Some(self.kb.stats())
+    }
+}
+////////////////////////////////////////////////////////////
+
+// Monte Carlo Agent Impl //////////////////////////////////
+/// Simulations the Monte Carlo agent runs per step by default.
+pub const DEFAULT_MCTS_ITERATIONS: usize = 500;
+
+/// Number of hidden worlds sampled from the belief state
+/// before each decision. Simulations cycle through clones of
+/// these particles.
+const PARTICLES: usize = 64;
+const MAX_DEPTH: usize = 100;
+const DISCOUNT: f64 = 0.98;
+/// UCB1 exploration constant. Kept low so that deadly actions
+/// stop being tried soon after they first lead to a death.
+const EXPLORATION: f64 = 50.0;
+/// Prior probability of a room containing a pit.
+const PIT_PROB: f64 = 0.2;
+/// Visits an action needs before its value is trusted over the
+/// default policy's.
+const MIN_VISITS: usize = 10;
+/// Times the agent may stand in a room before it stops searching
+/// there and sticks to the default policy, which never loops.
+const MAX_REVISITS: usize = 4;
+
+#[derive(Clone, Copy)]
+struct SimState {
+    has_arrow: bool,
+    has_gold: bool,
+}
+
+/// Observation key of the search tree: the agent position and
+/// a bitmask of the room senses.
+type ObsKey = (Pos, u8);
+
+#[derive(Default)]
+struct BeliefNode {
+    visits: usize,
+    total: f64,
+    children: HashMap<Action, ActionNode>,
+}
+
+impl BeliefNode {
+    /// Value of the node when acting best from there on: the mean
+    /// return of the simulations that went through it, unless one
+    /// of the sufficiently explored actions is known to do better.
+    fn value(&self) -> f64 {
+        self.children
+            .values()
+            .filter(|child| child.visits >= MIN_VISITS)
+            .map(ActionNode::value)
+            .fold(self.total / self.visits as f64, f64::max)
+    }
+}
+
+#[derive(Default)]
+struct ActionNode {
+    visits: usize,
+    total: f64,
+    total_sq: f64,
+    reward: f64,
+    children: HashMap<ObsKey, BeliefNode>,
+}
+
+impl ActionNode {
+    fn mean(&self) -> f64 {
+        self.total / self.visits as f64
+    }
+
+    /// Expected return of the action when acting best afterwards.
+    /// Unlike `mean`, it isn't dragged down by the exploratory
+    /// moves tried further down the tree.
+    fn value(&self) -> f64 {
+        let n = self.visits as f64;
+        let future: f64 = self
+            .children
+            .values()
+            .filter(|child| child.visits > 0)
+            .map(|child| child.visits as f64 / n * child.value())
+            .sum();
+        self.reward / n + DISCOUNT * future
+    }
+
+    fn variance_of_mean(&self) -> f64 {
+        let n = self.visits as f64;
+        let variance = (self.total_sq / n - self.mean().powi(2)).max(0.0);
+        variance / n
+    }
+}
+
+/// What the default policy knows about the world while playing.
+struct Explorer {
+    visited: HashSet<Pos>,
+    safe: HashSet<Pos>,
+    has_gold: bool,
+}
+
+/// A planning agent that runs POMCP (UCT over belief states).
+///
+/// Before each decision, it samples hidden worlds (pit, Wumpus
+/// and gold layouts) consistent with everything it perceived so
+/// far, simulates them with cloned `Environment`s and picks the
+/// action with the best expected return after `iterations`
+/// simulations.
+pub struct MonteCarloAgent {
+    start_pos: Pos,
+    grid_rows: usize,
+    grid_cols: usize,
+    iterations: usize,
+    /// Rules the simulated worlds are scored with
+    scoring: ScoringRules,
+    /// Seed the simulations are drawn from, on every episode
+    seed: u64,
+    rng: StdRng,
+    /// Visited rooms along with the senses perceived there
+    known: HashMap<Pos, HashSet<Sense>>,
+    /// Times the agent stood in each room this episode
+    revisits: HashMap<Pos, usize>,
+    has_arrow: bool,
+    wumpus_alive: bool,
+    has_gold: bool,
+}
+
+impl MonteCarloAgent {
+    /// Creates an agent running `iterations` simulations per step.
+    /// Fails when `iterations` is 0, or when `start_pos` lies
+    /// outside the grid.
+    pub fn new(
+        start_pos: &Pos,
+        grid_rows: usize,
+        grid_cols: usize,
+        iterations: usize,
+    ) -> Result<Self, Error> {
+        if iterations == 0 {
+            return Err(Error::NoIterations);
+        }
+        if start_pos.row >= grid_rows || start_pos.col >= grid_cols {
+            return Err(Error::OffGrid(start_pos.clone()));
+        }
+        let seed = rand::rng().random();
+        Ok(Self {
+            start_pos: start_pos.clone(),
+            grid_rows,
+            grid_cols,
+            iterations,
+            scoring: ScoringRules::default(),
+            seed,
+            rng: StdRng::seed_from_u64(seed),
+            known: HashMap::new(),
+            revisits: HashMap::new(),
+            has_arrow: true,
+            wumpus_alive: true,
+            has_gold: false,
+        })
+    }
+
+    /// Makes the agent plan with `scoring`, the rules of the
+    /// environment it plays in.
+    pub fn with_scoring(mut self, scoring: ScoringRules) -> Self {
+        self.scoring = scoring;
+        self
+    }
+
+    /// Makes the agent draw its simulations from `seed` instead of
+    /// a random one, so that it plays the same way on every
+    /// episode of the same world.
+    pub fn with_seed(mut self, seed: u64) -> Self {
+        self.seed = seed;
+        self.rng = StdRng::seed_from_u64(seed);
+        self
+    }
+
+    fn is_direction_valid(&self, pos: &Pos, direction: &Direction) -> bool {
+        match direction {
+            Direction::North => pos.row > 0,
+            Direction::South => pos.row < self.grid_rows - 1,
+            Direction::East => pos.col < self.grid_cols - 1,
+            Direction::West => pos.col > 0,
+        }
+    }
+
+    /// Returns the position next to `pos` towards `direction`, or
+    /// `None` when a wall is in the way.
+    fn neighbor(&self, pos: &Pos, direction: &Direction) -> Option<Pos> {
+        match self.is_direction_valid(pos, direction) {
+            true => pos.try_add(direction).ok(),
+            false => None,
+        }
+    }
+
+    fn neighborhood(&self, pos: &Pos) -> Vec<Pos> {
+        ALL_DIRECTIONS
+            .iter()
+            .filter_map(|dir| self.neighbor(pos, dir))
+            .collect()
+    }
+
+    fn perceived(&self, pos: &Pos, sense: &Sense) -> bool {
+        self.known[pos].contains(sense)
+    }
+
+    /// Samples a hidden world consistent with the percepts
+    /// gathered so far and returns it as an environment where the
+    /// agent stands at `pos`.
+    ///
+    /// This isn't rejection sampling: the Wumpus is drawn
+    /// uniformly among the rooms matching the stenches, but pits
+    /// are drawn from their prior and then repaired, adding one
+    /// next to every breezy room left without any. Worlds are
+    /// thus consistent with the percepts, yet only roughly
+    /// distributed like the belief state.
+    fn sample_world<R: Rng>(&self, pos: &Pos, rng: &mut R) -> Result<Environment, Error> {
+        let mut grid = Grid::new(self.grid_rows, self.grid_cols);
+        let unknown: Vec<Pos> = (0..self.grid_rows)
+            .flat_map(|i| (0..self.grid_cols).map(move |j| Pos::new(i, j)))
+            .filter(|p| !self.known.contains_key(p))
+            .collect();
+
+        /* The Wumpus can only be in an unvisited room whose
+        neighborhood matches exactly the stenchy rooms we visited */
+        let mut wumpus_pos = None;
+        if self.wumpus_alive {
+            let candidates: Vec<&Pos> = unknown
+                .iter()
+                .filter(|cand| {
+                    let neighbors = self.neighborhood(cand);
+                    self.known.keys().all(|visited| {
+                        neighbors.contains(visited) == self.perceived(visited, &Sense::Stench)
+                    })
+                })
+                .collect();
+            // A living Wumpus always lies among the candidates
+            wumpus_pos = candidates.iter().choose(rng).map(|pos| (*pos).clone());
+        }
+
+        /* Rooms next to a visited room that is not breezy cannot
+        contain a pit, the remaining ones get one with `PIT_PROB` */
+        let pit_free: HashSet<Pos> = self
+            .known
+            .keys()
+            .filter(|visited| !self.perceived(visited, &Sense::Breeze))
+            .flat_map(|visited| self.neighborhood(visited))
+            .collect();
+        let mut pits: HashSet<Pos> = unknown
+            .iter()
+            .filter(|p| !pit_free.contains(p) && Some(*p) != wumpus_pos.as_ref())
+            .filter(|_| rng.random_bool(PIT_PROB))
+            .cloned()
+            .collect();
+
+        /* Repair breezy rooms that ended up without a pit around */
+        for visited in self.known.keys() {
+            if !self.perceived(visited, &Sense::Breeze) {
+                continue;
+            }
+            let neighbors = self.neighborhood(visited);
+            if neighbors.iter().any(|np| pits.contains(np)) {
+                continue;
+            }
+            let candidate = neighbors
+                .iter()
+                .filter(|np| {
+                    !self.known.contains_key(np)
+                        && !pit_free.contains(np)
+                        && Some(*np) != wumpus_pos.as_ref()
+                })
+                .choose(rng);
+            if let Some(np) = candidate {
+                pits.insert(np.clone());
+            }
+        }
+
+        for pit in &pits {
+            grid.mut_room_at(pit).set_kind(RoomKind::Pit);
+        }
+        if let Some(wumpus_pos) = &wumpus_pos {
+            grid.mut_room_at(wumpus_pos).set_kind(RoomKind::Wumpus);
+        }
+
+        if !self.has_gold {
+            let glittering = self
+                .known
+                .keys()
+                .find(|visited| self.perceived(visited, &Sense::Glitter));
+            let gold_pos = match glittering {
+                Some(visited) => Some(visited),
+                None => unknown
+                    .iter()
+                    .filter(|p| !pits.contains(p) && Some(*p) != wumpus_pos.as_ref())
+                    .choose(rng),
+            };
+            if let Some(gold_pos) = gold_pos {
+                grid.mut_room_at(gold_pos).set_kind(RoomKind::Gold);
+            }
+        }
+
+        // The sampled world starts over from the current room, so
+        // the limits of the real episode don't carry over to it
+        let scoring = ScoringRules {
+            step_limit: None,
+            repeat_limit: None,
+            ..self.scoring.clone()
+        };
+        let mut env = Environment::from_grid(grid, self.start_pos.clone()).with_scoring(scoring);
+        env.set_agent_position(pos.clone())?;
+        Ok(env)
+    }
+
+    fn legal_actions(&self, env: &Environment, state: SimState) -> Vec<Action> {
+        let obs = env.observation();
+        let mut actions: Vec<Action> = Vec::new();
+        if obs.senses().contains(&Sense::Glitter) {
+            actions.push(Action::Grab);
+        }
+        if *obs.position() == self.start_pos {
+            actions.push(Action::Climb);
+        }
+        for dir in ALL_DIRECTIONS.iter() {
+            if obs.directions().contains(dir) {
+                actions.push(Action::Move(dir.clone()));
+                if state.has_arrow && self.wumpus_alive {
+                    actions.push(Action::Shoot(dir.clone()));
+                }
+            }
+        }
+        actions
+    }
+
+    /// Runs one simulation from `node` down the search tree,
+    /// choosing actions with UCB1. Returns the discounted return
+    /// of the simulation.
+    fn simulate<R: Rng>(
+        &self,
+        env: &mut Environment,
+        node: &mut BeliefNode,
+        state: SimState,
+        depth: usize,
+        rng: &mut R,
+    ) -> Result<f64, Error> {
+        if depth == 0 {
+            return Ok(0.0);
+        }
+
+        let log_visits = ((node.visits + 1) as f64).ln();
+        let action = self
+            .legal_actions(env, state)
+            .into_iter()
+            .map(|action| {
+                let ucb = match node.children.get(&action) {
+                    Some(child) if child.visits > 0 => {
+                        child.mean() + EXPLORATION * (log_visits / child.visits as f64).sqrt()
+                    }
+                    _ => f64::INFINITY,
+                };
+                (action, ucb + rng.random::<f64>())
+            })
+            .max_by(|a, b| a.1.total_cmp(&b.1))
+            .map(|(action, _)| action)
+            .unwrap();
+
+        self.simulate_action(env, node, action, state, depth, rng)
+    }
+
+    /// Takes `action` in the simulated `env`, then either expands
+    /// a new node evaluated by a rollout or keeps descending the
+    /// search tree.
+    fn simulate_action<R: Rng>(
+        &self,
+        env: &mut Environment,
+        node: &mut BeliefNode,
+        action: Action,
+        state: SimState,
+        depth: usize,
+        rng: &mut R,
+    ) -> Result<f64, Error> {
+        let before = env.score();
+        let result = env.step(&action)?;
+        let reward = (env.score() - before) as f64;
+        let state = SimState {
+            has_arrow: state.has_arrow && !matches!(action, Action::Shoot(_)),
+            has_gold: state.has_gold || matches!(result, ActionResult::Grabbed(_)),
+        };
+
+        let child = node.children.entry(action).or_default();
+        let value = match result {
+            ActionResult::GameOver => reward,
+            _ => {
+                let key = obs_key(env.observation());
+                let grandchild = child.children.entry(key).or_default();
+                let future = match grandchild.visits {
+                    0 => {
+                        grandchild.visits += 1;
+                        self.rollout(env, state, depth - 1)?
+                    }
+                    _ => self.simulate(env, grandchild, state, depth - 1, rng)?,
+                };
+                grandchild.total += future;
+                reward + DISCOUNT * future
+            }
+        };
+
+        node.visits += 1;
+        child.visits += 1;
+        child.total += value;
+        child.total_sq += value * value;
+        child.reward += reward;
+        Ok(value)
+    }
+
+    fn explorer(&self, has_gold: bool) -> Explorer {
+        let visited: HashSet<Pos> = self.known.keys().cloned().collect();
+        let mut safe = visited.clone();
+        for (pos, senses) in &self.known {
+            if !senses.contains(&Sense::Breeze) && !senses.contains(&Sense::Stench) {
+                safe.extend(self.neighborhood(pos));
+            }
+        }
+        Explorer {
+            visited,
+            safe,
+            has_gold,
+        }
+    }
+
+    /// The default policy, as a cautious explorer would play: grab
+    /// any gold found, otherwise walk to the nearest unvisited room
+    /// that is safe according to the percepts, and climb out once
+    /// there's none left.
+    fn explorer_action(&self, explorer: &mut Explorer, obs: &Observation) -> Option<Action> {
+        let pos = obs.position();
+        let senses = obs.senses();
+        explorer.visited.insert(pos.clone());
+        if !senses.contains(&Sense::Breeze) && !senses.contains(&Sense::Stench) {
+            explorer.safe.extend(self.neighborhood(pos));
+        }
+
+        if senses.contains(&Sense::Glitter) {
+            return Some(Action::Grab);
+        }
+        let frontier = match explorer.has_gold {
+            true => None,
+            false => self.first_move(&explorer.safe, pos, |p| !explorer.visited.contains(p)),
+        };
+        match frontier {
+            Some(dir) => Some(Action::Move(dir)),
+            None => match self.first_move(&explorer.safe, pos, |p| *p == self.start_pos) {
+                Some(dir) => Some(Action::Move(dir)),
+                None if *pos == self.start_pos => Some(Action::Climb),
+                None => None,
+            },
+        }
+    }
+
+    /// Estimates the value of a simulated world by following the
+    /// default policy in it.
+    fn rollout(&self, env: &mut Environment, state: SimState, depth: usize) -> Result<f64, Error> {
+        let mut explorer = self.explorer(state.has_gold);
+        let mut total = 0.0;
+        let mut discount = 1.0;
+        for _ in 0..depth {
+            let Some(action) = self.explorer_action(&mut explorer, env.observation()) else {
+                break;
+            };
+
+            let before = env.score();
+            let result = env.step(&action)?;
+            total += discount * (env.score() - before) as f64;
+            discount *= DISCOUNT;
+            match result {
+                ActionResult::GameOver => break,
+                ActionResult::Grabbed(_) => explorer.has_gold = true,
+                _ => {}
+            }
+        }
+        Ok(total)
+    }
+
+    /// Breadth-first search through the `safe` rooms for the
+    /// closest room other than `from` satisfying `goal`. Returns
+    /// the first move of the path leading to it.
+    fn first_move<F: Fn(&Pos) -> bool>(
+        &self,
+        safe: &HashSet<Pos>,
+        from: &Pos,
+        goal: F,
+    ) -> Option<Direction> {
+        let mut queue: VecDeque<(Pos, Direction)> = VecDeque::new();
+        let mut seen: HashSet<Pos> = HashSet::from([from.clone()]);
+        for dir in ALL_DIRECTIONS.iter() {
+            if let Some(next) = self.neighbor(from, dir) {
+                queue.push_back((next, dir.clone()));
+            }
+        }
+
+        while let Some((pos, first)) = queue.pop_front() {
+            if !safe.contains(&pos) || !seen.insert(pos.clone()) {
+                continue;
+            }
+            if goal(&pos) {
+                return Some(first);
+            }
+            for dir in ALL_DIRECTIONS.iter() {
+                if let Some(next) = self.neighbor(&pos, dir) {
+                    queue.push_back((next, first.clone()));
+                }
+            }
+        }
+        None
+    }
+
+    /// Picks the next action. Root actions are evaluated in turn on
+    /// the same sampled worlds, so that their mean returns differ
+    /// by the action taken rather than by the luck of the draw, and
+    /// each subtree below them is searched with UCT.
+    ///
+    /// The default policy's action is kept unless the search finds
+    /// one that is significantly better, which keeps the agent from
+    /// wandering between rooms of nearly equal value.
+    pub fn ask_for_action(&mut self, obs: &Observation) -> Result<Action, Error> {
+        let mut rng = self.rng.clone();
+        let action = self.search(obs, &mut rng);
+        self.rng = rng;
         action
     }
+
+    fn search<R: Rng>(&self, obs: &Observation, rng: &mut R) -> Result<Action, Error> {
+        let pos = obs.position();
+        let default_action = self.explorer_action(&mut self.explorer(self.has_gold), obs);
+        if self.revisits.get(pos).is_some_and(|n| *n > MAX_REVISITS)
+            && let Some(action) = default_action
+        {
+            return Ok(action);
+        }
+        let state = SimState {
+            has_arrow: self.has_arrow,
+            has_gold: self.has_gold,
+        };
+
+        let nparticles = (self.iterations / 4).clamp(1, PARTICLES);
+        let mut particles = (0..nparticles)
+            .map(|_| self.sample_world(pos, rng))
+            .collect::<Result<Vec<_>, _>>()?;
+        let starts: Vec<Snapshot> = particles.iter().map(|env| env.snapshot()).collect();
+
+        let actions = self.legal_actions(&particles[0], state);
+        let mut root = BeliefNode::default();
+        for i in 0..self.iterations {
+            let action = actions[i % actions.len()].clone();
+            // Each simulation starts over from the sampled world
+            let particle = (i / actions.len()) % particles.len();
+            let env = &mut particles[particle];
+            env.restore(&starts[particle]);
+            self.simulate_action(env, &mut root, action, state, MAX_DEPTH, rng)?;
+        }
+
+        let best = root
+            .children
+            .iter()
+            .filter(|(_, child)| child.visits > 0)
+            .max_by(|a, b| a.1.value().total_cmp(&b.1.value()));
+        let default = default_action.and_then(|action| root.children.get_key_value(&action));
+
+        Ok(match (best, default) {
+            (Some((action, node)), Some((default_action, default_node))) => {
+                let margin =
+                    2.0 * (node.variance_of_mean() + default_node.variance_of_mean()).sqrt();
+                match node.value() - default_node.value() > margin {
+                    true => action.clone(),
+                    false => default_action.clone(),
+                }
+            }
+            (Some((action, _)), None) => action.clone(),
+            (None, _) => Action::Climb,
+        })
+    }
+}
+
+impl Agent for MonteCarloAgent {
+    fn act(&mut self, obs: &Observation) -> Result<Action, Error> {
+        let room_senses = obs
+            .senses()
+            .iter()
+            .filter(|s| matches!(s, Sense::Breeze | Sense::Stench | Sense::Glitter))
+            .cloned()
+            .collect();
+        self.known.insert(obs.position().clone(), room_senses);
+        *self.revisits.entry(obs.position().clone()).or_default() += 1;
+
+        self.ask_for_action(obs)
+    }
+
+    fn on_episode_start(&mut self, _obs: &Observation) {
+        self.rng = StdRng::seed_from_u64(self.seed);
+        self.known.clear();
+        self.revisits.clear();
+        self.has_arrow = true;
+        self.wumpus_alive = true;
+        self.has_gold = false;
+    }
+
+    fn on_result(&mut self, action: &Action, result: &ActionResult, _reward: isize) {
+        if let Action::Shoot(_) = action {
+            self.has_arrow = false;
+        }
+        match result {
+            ActionResult::Sense(Sense::Scream) => self.wumpus_alive = false,
+            ActionResult::Grabbed(_) => self.has_gold = true,
+            _ => {}
+        }
+    }
+}
+
+fn obs_key(obs: &Observation) -> ObsKey {
+    let bits = [Sense::Breeze, Sense::Stench, Sense::Glitter]
+        .iter()
+        .enumerate()
+        .filter(|(_, sense)| obs.senses().contains(sense))
+        .fold(0, |acc, (i, _)| acc | (1 << i));
+    (obs.position().clone(), bits)
 }
 ////////////////////////////////////////////////////////////
 
@@ -441,22 +1515,190 @@ fn make_stench_atomic(pos: &Pos) -> Statement {
     Statement::Atomic(format!("St_{},{}", pos.row, pos.col))
 }
 
-fn make_disjuncts(stmts: &[Statement]) -> Option<Statement> {
-    match stmts.first() {
-        Some(stmt) => match make_disjuncts(&stmts[1..]) {
-            Some(rhs) => Some(Statement::OrClause(stmt.clone().boxed(), rhs.boxed())),
-            None => Some(stmt.to_owned()),
-        },
-        None => None,
+/// Joins `first` and `rest` into `first | (rest[0] | ...)`, so
+/// that there's always a statement to join.
+fn make_disjuncts(first: Statement, rest: Vec<Statement>) -> Statement {
+    let rhs = rest
+        .into_iter()
+        .rev()
+        .reduce(|rhs, stmt| Statement::OrClause(stmt.boxed(), rhs.boxed()));
+    match rhs {
+        Some(rhs) => Statement::OrClause(first.boxed(), rhs.boxed()),
+        None => first,
     }
 }
 
-fn make_conjuncts(stmts: &[Statement]) -> Option<Statement> {
-    match stmts.first() {
-        Some(stmt) => match make_conjuncts(&stmts[1..]) {
-            Some(rhs) => Some(Statement::AndClause(stmt.clone().boxed(), rhs.boxed())),
-            None => Some(stmt.to_owned()),
-        },
-        None => None,
+/// Joins `first` and `rest` into `first & (rest[0] & ...)`, so
+/// that there's always a statement to join.
+fn make_conjuncts(first: Statement, rest: Vec<Statement>) -> Statement {
+    let rhs = rest
+        .into_iter()
+        .rev()
+        .reduce(|rhs, stmt| Statement::AndClause(stmt.boxed(), rhs.boxed()));
+    match rhs {
+        Some(rhs) => Statement::AndClause(first.boxed(), rhs.boxed()),
+        None => first,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::bench::play_episode;
+    use crate::env::{GridType, Outcome};
+    use crate::map::Map;
+
+    /// Lets `agent` play `env` through, returning how it ended.
+    fn play(env: &mut Environment, agent: &mut dyn Agent) -> Outcome {
+        play_episode(env, agent, |_, _, _| Ok(())).unwrap();
+        env.outcome().cloned().unwrap()
+    }
+
+    #[test]
+    fn finds_the_walls_and_the_gold_of_an_unknown_world() {
+        let mut env = Environment::new(GridType::Classic);
+        let mut agent = KnowledgeBasedAgent::with_unknown_size(env.start_position());
+        assert_eq!((agent.grid_rows, agent.grid_cols), (None, None));
+
+        assert_eq!(play(&mut env, &mut agent), Outcome::Escaped { gold: 1 });
+        // The gold lies west of the east wall, which is never
+        // reached, while the south wall is bumped into right away
+        assert_eq!((agent.grid_rows, agent.grid_cols), (Some(4), None));
+        // The room beyond it was ruled out rather than left as a
+        // possible Wumpus den
+        assert_eq!(
+            agent.kb.ask(&make_wumpus_atomic(&Pos::new(4, 0))),
+            Some(false)
+        );
+        assert!(agent.beliefs().keys().all(|pos| pos.row < 4));
+
+        let map = Map::parse("W _ _\n_ _ _\nA _ G\n").unwrap();
+        let mut env = map.into_environment();
+        let mut agent = KnowledgeBasedAgent::with_unknown_size(env.start_position());
+        assert_eq!(play(&mut env, &mut agent), Outcome::Escaped { gold: 1 });
+        assert_eq!((agent.grid_rows, agent.grid_cols), (Some(3), Some(3)));
+        // With both walls known, the Wumpus axiom is the one of an
+        // agent told the size, without `W_?`
+        let axiom = agent.wumpus_axiom.clone().unwrap();
+        assert!(!axiom.to_string().contains("W_?"), "{}", axiom);
+        assert_eq!(agent.beliefs().len(), 9);
+    }
+
+    /// Lets `agent` take a single step of `env`.
+    fn step(env: &mut Environment, agent: &mut dyn Agent) -> Action {
+        let action = agent.act(env.observation()).unwrap();
+        let result = env.step_with_reward(&action).unwrap();
+        agent.on_result(&action, &result.info, result.reward);
+        action
+    }
+
+    #[test]
+    fn explains_its_decisions() {
+        let mut env = Environment::new(GridType::Classic);
+        let mut agent = KnowledgeBasedAgent::new(env.start_position(), 4, 4).unwrap();
+        assert_eq!(Agent::explain(&agent), None);
+
+        // Nothing is perceived at the start, so both rooms next to
+        // it are proven safe from the start being void
+        let proof = |row, col| Reason::Proven {
+            fact: make_safe_atomic(&Pos::new(row, col)),
+            premises: vec![make_void_atomic(&Pos::new(3, 0))],
+        };
+        assert_eq!(step(&mut env, &mut agent), Action::Move(Direction::East));
+        assert_eq!(Agent::explain(&agent).unwrap().reasons(), [proof(3, 1)]);
+
+        // The breeze next to the pit leaves no safe room to go on
+        // to, so the agent heads back
+        assert_eq!(step(&mut env, &mut agent), Action::Move(Direction::West));
+        let unknown = Rejection::UnknownRisk {
+            pit: true,
+            wumpus: false,
+        };
+        assert_eq!(
+            Agent::explain(&agent).unwrap().reasons(),
+            [
+                Reason::Rejected {
+                    direction: Direction::West,
+                    cause: Rejection::Visited,
+                },
+                Reason::Rejected {
+                    direction: Direction::North,
+                    cause: unknown.clone(),
+                },
+                Reason::Rejected {
+                    direction: Direction::East,
+                    cause: unknown,
+                },
+                Reason::Backtracking { depth: 1 },
+            ]
+        );
+
+        assert_eq!(step(&mut env, &mut agent), Action::Move(Direction::North));
+        assert_eq!(Agent::explain(&agent).unwrap().reasons(), [proof(2, 0)]);
+    }
+
+    #[test]
+    fn tells_what_it_believes_of_each_room() {
+        let mut env = Environment::new(GridType::Classic);
+        let mut agent = KnowledgeBasedAgent::new(env.start_position(), 4, 4).unwrap();
+        // East into the breeze, back west, then north into the stench
+        for _ in 0..3 {
+            step(&mut env, &mut agent);
+        }
+        assert_eq!(env.agent_position(), &Pos::new(2, 0));
+        // The agent takes in what it perceives there as it acts
+        agent.act(env.observation()).unwrap();
+
+        let beliefs = Agent::beliefs(&mut agent).unwrap();
+        assert_eq!(beliefs.len(), 16);
+        for (row, col, belief) in [
+            (3, 0, Belief::Visited),
+            (3, 1, Belief::Visited),
+            (2, 0, Belief::Visited),
+            // Neither breezy nor stenchy rooms are next to it
+            (2, 1, Belief::Safe),
+            (3, 2, Belief::PossibleHazard),
+            (1, 0, Belief::PossibleHazard),
+            (0, 0, Belief::Unknown),
+            (3, 3, Belief::Unknown),
+        ] {
+            assert_eq!(beliefs[&Pos::new(row, col)], belief, "({}, {})", row, col);
+        }
+        // Working out beliefs doesn't count as reasoning to act
+        let stats = agent.kb.stats();
+        Agent::beliefs(&mut agent);
+        assert_eq!(agent.kb.stats(), stats);
+    }
+
+    #[test]
+    fn leaves_out_senses_saying_nothing_of_the_room() {
+        let agent = KnowledgeBasedAgent::new(&Pos::new(0, 0), 3, 3).unwrap();
+        let mut env = Map::parse("A _ _\nW _ _\n_ _ G\n")
+            .unwrap()
+            .into_environment();
+        env.step(&Action::Move(Direction::East)).unwrap();
+        env.step(&Action::Climb).unwrap();
+        assert!(env.observation().senses().contains(&Sense::Ceil));
+        assert_eq!(
+            agent.make_percept_stmt(env.observation()),
+            make_void_atomic(&Pos::new(0, 1))
+        );
+
+        env.step(&Action::Move(Direction::West)).unwrap();
+        env.step(&Action::Move(Direction::North)).unwrap();
+        let mut obs = env.observation().clone();
+        obs.mut_senses().insert(Sense::Bump(Direction::North));
+        assert_eq!(
+            agent.make_percept_stmt(&obs),
+            make_conjuncts(
+                make_stench_atomic(&Pos::new(0, 0)),
+                vec![make_breeze_atomic(&Pos::new(0, 0)).negate()]
+            )
+        );
+
+        // The agent goes on acting after a refused climb
+        let mut agent = KnowledgeBasedAgent::new(&Pos::new(0, 0), 3, 3).unwrap();
+        obs.mut_senses().insert(Sense::Ceil);
+        assert!(agent.act(&obs).is_ok());
     }
 }
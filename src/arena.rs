@@ -0,0 +1,372 @@
+use std::{cmp::Ordering, io::Write};
+
+use crate::{
+    agents::{AgentKind, Contender},
+    bench::{self, BenchConfig, BenchReport, CSV_HEADER, EpisodeReport},
+    env::Ruleset,
+    error::Error,
+    events::json_string,
+    scoring::{self, ScoringRules},
+    world::WorldConfig,
+};
+
+/// A comparison of agents, each run over the same seeded random
+/// worlds.
+#[derive(Debug, Clone)]
+pub struct ArenaConfig {
+    /// The agents to compare, which can be built by any means
+    pub agents: Vec<Contender>,
+    /// Number of worlds every agent runs over
+    pub episodes: usize,
+    /// Seed of the first world, the next ones following in order
+    pub first_seed: u64,
+    /// Number of worlds run at once
+    pub threads: usize,
+    /// How the worlds are generated
+    pub world: WorldConfig,
+    /// The ruleset every agent plays by
+    pub ruleset: Ruleset,
+    /// The rules the episodes are scored with
+    pub scoring: ScoringRules,
+}
+
+impl ArenaConfig {
+    /// Compares every registered agent over `episodes` worlds,
+    /// with the step limit fit for the largest world.
+    pub fn new(episodes: usize) -> Self {
+        let world = WorldConfig::default();
+        Self {
+            agents: AgentKind::registered()
+                .into_iter()
+                .map(Contender::from)
+                .collect(),
+            episodes,
+            first_seed: 1,
+            threads: 1,
+            scoring: ScoringRules {
+                step_limit: Some(scoring::default_step_limit(
+                    *world.rows.end(),
+                    *world.cols.end(),
+                )),
+                ..ScoringRules::default()
+            },
+            world,
+            ruleset: Ruleset::default(),
+        }
+    }
+
+    /// The benchmark running `agent` over the worlds of the arena.
+    fn bench(&self, agent: Contender) -> BenchConfig {
+        BenchConfig {
+            agent,
+            episodes: self.episodes,
+            first_seed: self.first_seed,
+            threads: self.threads,
+            world: self.world.clone(),
+            ruleset: self.ruleset,
+            scoring: self.scoring.clone(),
+        }
+    }
+}
+
+/// How an agent did in the arena.
+#[derive(Debug, Clone)]
+pub struct Entry {
+    /// Name of the agent
+    pub name: String,
+    /// How it did over the worlds
+    pub report: BenchReport,
+}
+
+/// How two agents did against each other, world by world.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct HeadToHead {
+    /// Worlds where the first agent scored higher
+    pub wins: usize,
+    /// Worlds where the second agent scored higher
+    pub losses: usize,
+    /// Worlds where both scored the same
+    pub ties: usize,
+}
+
+/// A world where the agents scored far apart.
+#[derive(Debug, Clone)]
+pub struct Divergence {
+    /// Seed of the world
+    pub seed: u64,
+    /// Gap between the best and the worst score
+    pub spread: isize,
+    /// How each agent did on it, in the order of the arena's entries
+    pub episodes: Vec<EpisodeReport>,
+}
+
+/// The results of every agent of an arena, over the same worlds.
+#[derive(Debug, Clone)]
+pub struct ArenaReport {
+    entries: Vec<Entry>,
+}
+
+impl ArenaReport {
+    /// The agents in the order they were run.
+    pub fn entries(&self) -> &[Entry] {
+        &self.entries
+    }
+
+    /// The agents from the highest mean score to the lowest.
+    pub fn leaderboard(&self) -> Vec<&Entry> {
+        let mut entries: Vec<&Entry> = self.entries.iter().collect();
+        entries.sort_by(|a, b| {
+            b.report
+                .mean_score()
+                .partial_cmp(&a.report.mean_score())
+                .unwrap_or(Ordering::Equal)
+        });
+        entries
+    }
+
+    /// How the entry of index `a` did against the entry of index
+    /// `b`, compared on their scores in each world.
+    pub fn head_to_head(&self, a: usize, b: usize) -> HeadToHead {
+        let mut result = HeadToHead::default();
+        let episodes = self.entries[a].report.episodes();
+        for (ours, theirs) in episodes.iter().zip(self.entries[b].report.episodes()) {
+            match ours.score.cmp(&theirs.score) {
+                Ordering::Greater => result.wins += 1,
+                Ordering::Less => result.losses += 1,
+                Ordering::Equal => result.ties += 1,
+            }
+        }
+        result
+    }
+
+    /// The `count` worlds where the agents' scores spread the
+    /// most, widest first, leaving out worlds they all scored the
+    /// same on.
+    pub fn divergent_worlds(&self, count: usize) -> Vec<Divergence> {
+        let Some(first) = self.entries.first() else {
+            return Vec::new();
+        };
+        let mut worlds: Vec<Divergence> = (0..first.report.episodes().len())
+            .map(|i| {
+                let episodes: Vec<EpisodeReport> = self
+                    .entries
+                    .iter()
+                    .map(|entry| entry.report.episodes()[i].clone())
+                    .collect();
+                let best = episodes.iter().map(|e| e.score).max().unwrap_or(0);
+                let worst = episodes.iter().map(|e| e.score).min().unwrap_or(0);
+                Divergence {
+                    seed: episodes[0].seed.expect("arena worlds are seeded"),
+                    spread: best - worst,
+                    episodes,
+                }
+            })
+            .filter(|world| world.spread > 0)
+            .collect();
+        worlds.sort_by(|a, b| b.spread.cmp(&a.spread).then(a.seed.cmp(&b.seed)));
+        worlds.truncate(count);
+        worlds
+    }
+
+    /// Writes one CSV row per agent and episode, after a header
+    /// row.
+    pub fn write_csv(&self, out: &mut impl Write) -> std::io::Result<()> {
+        writeln!(out, "agent,{}", CSV_HEADER)?;
+        for entry in &self.entries {
+            for episode in entry.report.episodes() {
+                write!(out, "{},", csv_field(&entry.name))?;
+                episode.write_csv(out)?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Writes the leaderboard as one JSON object per agent and
+    /// line, best first.
+    pub fn write_json(&self, out: &mut impl Write) -> std::io::Result<()> {
+        for (rank, entry) in self.leaderboard().into_iter().enumerate() {
+            let report = &entry.report;
+            writeln!(
+                out,
+                "{{\"rank\":{},\"agent\":{},\"mean_score\":{:.2},\"score_margin\":{:.2},\
+                 \"win_rate\":{:.4},\"win_rate_margin\":{:.4},\"mean_steps\":{:.2}}}",
+                rank + 1,
+                json_string(&entry.name),
+                report.mean_score(),
+                report.score_margin(),
+                report.win_rate(),
+                report.win_rate_margin(),
+                report.mean_steps()
+            )?;
+        }
+        Ok(())
+    }
+}
+
+/// Runs every agent of `config` over its worlds, one agent after
+/// the other. Fails on the first episode that can't be played
+/// through.
+pub fn run(config: &ArenaConfig) -> Result<ArenaReport, Error> {
+    let entries = config
+        .agents
+        .iter()
+        .map(|agent| {
+            Ok(Entry {
+                name: agent.name().to_string(),
+                report: bench::run(&config.bench(agent.clone()), None)?,
+            })
+        })
+        .collect::<Result<Vec<_>, Error>>()?;
+    Ok(ArenaReport { entries })
+}
+
+/// Quotes `text` as a CSV field when it holds a separator, a
+/// quote or a line break.
+fn csv_field(text: &str) -> String {
+    match text.contains([',', '"', '\n', '\r']) {
+        true => format!("\"{}\"", text.replace('"', "\"\"")),
+        false => text.to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::*;
+    use crate::env::Outcome;
+
+    /// An entry for `name`, scoring `scores` on the worlds of
+    /// seeds 1, 2 and so on.
+    fn entry(name: &str, scores: &[isize]) -> Entry {
+        let episodes = scores
+            .iter()
+            .enumerate()
+            .map(|(i, &score)| EpisodeReport {
+                seed: Some(i as u64 + 1),
+                outcome: match score {
+                    s if s > 0 => Outcome::Escaped { gold: 1 },
+                    0 => Outcome::EmptyHanded,
+                    _ => Outcome::FellIntoPit,
+                },
+                score,
+                steps: 1,
+                inference: Duration::ZERO,
+            })
+            .collect();
+        Entry {
+            name: name.to_string(),
+            report: BenchReport::new(episodes),
+        }
+    }
+
+    fn report() -> ArenaReport {
+        ArenaReport {
+            entries: vec![
+                entry("climber, \"the safe\"", &[0, 0, 0, 0, 0]),
+                entry("kb", &[980, 0, -1005, 990, 0]),
+                entry("mcts", &[985, 970, -1005, 0, 0]),
+            ],
+        }
+    }
+
+    #[test]
+    fn ranks_agents_by_mean_score() {
+        let report = report();
+        let names: Vec<&str> = report
+            .leaderboard()
+            .iter()
+            .map(|entry| entry.name.as_str())
+            .collect();
+        assert_eq!(names, ["kb", "mcts", "climber, \"the safe\""]);
+        assert_eq!(report.entries()[0].name, "climber, \"the safe\"");
+    }
+
+    #[test]
+    fn bounds_the_mean_score_and_win_rate() {
+        let report = report();
+        let climber = &report.entries()[0].report;
+        assert_eq!(
+            (climber.score_margin(), climber.win_rate_margin()),
+            (0.0, 0.0)
+        );
+
+        let kb = &report.entries()[1].report;
+        assert_eq!((kb.mean_score(), kb.win_rate()), (193.0, 0.4));
+        assert!((kb.score_margin() - 728.672).abs() < 1e-3);
+        assert!((kb.win_rate_margin() - 0.4294).abs() < 1e-4);
+    }
+
+    #[test]
+    fn compares_agents_world_by_world() {
+        let report = report();
+        assert_eq!(
+            report.head_to_head(1, 2),
+            HeadToHead {
+                wins: 1,
+                losses: 2,
+                ties: 2,
+            }
+        );
+        assert_eq!(
+            report.head_to_head(2, 1),
+            HeadToHead {
+                wins: 2,
+                losses: 1,
+                ties: 2,
+            }
+        );
+
+        // The last world, scored the same by all, is left out
+        let worlds = report.divergent_worlds(10);
+        let seeds: Vec<(u64, isize)> = worlds.iter().map(|w| (w.seed, w.spread)).collect();
+        assert_eq!(seeds, [(3, 1005), (4, 990), (1, 985), (2, 970)]);
+        assert_eq!(worlds[0].episodes.len(), 3);
+        assert_eq!(report.divergent_worlds(2).len(), 2);
+    }
+
+    #[test]
+    fn escapes_agent_names() {
+        let report = report();
+        let mut csv = Vec::new();
+        report.write_csv(&mut csv).unwrap();
+        let csv = String::from_utf8(csv).unwrap();
+        assert!(
+            csv.lines()
+                .nth(1)
+                .unwrap()
+                .starts_with("\"climber, \"\"the safe\"\"\",1,empty-handed,"),
+            "{}",
+            csv
+        );
+
+        let mut json = Vec::new();
+        report.write_json(&mut json).unwrap();
+        let json = String::from_utf8(json).unwrap();
+        assert!(
+            json.lines()
+                .nth(2)
+                .unwrap()
+                .starts_with("{\"rank\":3,\"agent\":\"climber, \\\"the safe\\\"\","),
+            "{}",
+            json
+        );
+    }
+
+    #[test]
+    fn names_external_agents_after_their_command() {
+        let external = |program: &str, args: &[&str]| {
+            Contender::from(AgentKind::External {
+                program: program.to_string(),
+                args: args.iter().map(|arg| arg.to_string()).collect(),
+                timeout: Duration::from_secs(1),
+            })
+        };
+        assert_eq!(external("/tmp/my bots/bot.py", &[]).name(), "bot.py");
+        assert_eq!(
+            external("python3", &["bot.py", "--greedy"]).name(),
+            "python3 bot.py --greedy"
+        );
+        assert_eq!(Contender::from(AgentKind::KnowledgeBased).name(), "kb");
+    }
+}
@@ -0,0 +1,404 @@
+use std::{
+    io::Write,
+    sync::{
+        Mutex,
+        atomic::{AtomicUsize, Ordering},
+    },
+    time::{Duration, Instant},
+};
+
+use crate::{
+    agent::{Action, Agent},
+    agents::Contender,
+    env::{Environment, Outcome, Ruleset, StepResult, Truncation},
+    error::Error,
+    events::EventLog,
+    kb::KbStats,
+    scoring::{self, ScoringRules},
+    world::WorldConfig,
+};
+
+/// A batch of episodes to run an agent over, one per seeded
+/// random world.
+#[derive(Debug, Clone)]
+pub struct BenchConfig {
+    /// The agent to benchmark
+    pub agent: Contender,
+    /// Number of worlds to run it over
+    pub episodes: usize,
+    /// Seed of the first world, the next ones following in order
+    pub first_seed: u64,
+    /// Number of worlds run at once
+    pub threads: usize,
+    /// How the worlds are generated
+    pub world: WorldConfig,
+    /// The ruleset the agent plays by
+    pub ruleset: Ruleset,
+    /// The rules the episodes are scored with
+    pub scoring: ScoringRules,
+}
+
+impl BenchConfig {
+    /// Runs `agent` over `episodes` worlds from seed 1 on, one at
+    /// a time, with the default world and ruleset, and the default
+    /// scoring with the step limit fit for the largest world.
+    pub fn new(agent: impl Into<Contender>, episodes: usize) -> Self {
+        let world = WorldConfig::default();
+        Self {
+            agent: agent.into(),
+            episodes,
+            first_seed: 1,
+            threads: 1,
+            scoring: ScoringRules {
+                step_limit: Some(scoring::default_step_limit(
+                    *world.rows.end(),
+                    *world.cols.end(),
+                )),
+                ..ScoringRules::default()
+            },
+            world,
+            ruleset: Ruleset::default(),
+        }
+    }
+}
+
+/// How a single episode went.
+#[derive(Debug, Clone)]
+pub struct EpisodeReport {
+    /// Seed of the world, unless it wasn't randomly generated
+    pub seed: Option<u64>,
+    /// How the episode ended
+    pub outcome: Outcome,
+    /// Final score
+    pub score: isize,
+    /// Number of steps taken
+    pub steps: usize,
+    /// Time the agent spent choosing its actions
+    pub inference: Duration,
+}
+
+impl EpisodeReport {
+    /// Reports the episode `env` just finished, whose agent spent
+    /// `inference` choosing its actions.
+    pub fn new(env: &Environment, inference: Duration) -> Self {
+        Self {
+            seed: env.seed(),
+            outcome: env.outcome().cloned().expect("the episode is over"),
+            score: env.score(),
+            steps: env.steps(),
+            inference,
+        }
+    }
+
+    /// Whether the agent escaped with gold.
+    pub fn is_win(&self) -> bool {
+        matches!(self.outcome, Outcome::Escaped { .. })
+    }
+
+    /// Writes the episode as a CSV row, under `CSV_HEADER`.
+    pub fn write_csv(&self, out: &mut impl Write) -> std::io::Result<()> {
+        writeln!(
+            out,
+            "{},{},{},{},{:.3}",
+            self.seed.map(|seed| seed.to_string()).unwrap_or_default(),
+            outcome_label(&self.outcome),
+            self.score,
+            self.steps,
+            self.inference.as_secs_f64() * 1000.0
+        )
+    }
+
+    /// Writes the episode as a JSON object on its own line.
+    pub fn write_json(&self, out: &mut impl Write) -> std::io::Result<()> {
+        writeln!(
+            out,
+            "{{\"seed\":{},\"outcome\":\"{}\",\"score\":{},\"steps\":{},\"inference_ms\":{:.3}}}",
+            self.seed
+                .map(|seed| seed.to_string())
+                .unwrap_or("null".to_string()),
+            outcome_label(&self.outcome),
+            self.score,
+            self.steps,
+            self.inference.as_secs_f64() * 1000.0
+        )
+    }
+}
+
+/// Quantile of the normal distribution bounding 95% confidence
+/// intervals.
+const Z_95: f64 = 1.96;
+
+/// Header row of the CSV output of episodes.
+pub const CSV_HEADER: &str = "seed,outcome,score,steps,inference_ms";
+
+/// The episodes of a benchmark, ordered by seed, along with
+/// statistics over them.
+#[derive(Debug, Clone)]
+pub struct BenchReport {
+    episodes: Vec<EpisodeReport>,
+}
+
+impl BenchReport {
+    /// Reports over `episodes`, in any order.
+    pub fn new(mut episodes: Vec<EpisodeReport>) -> Self {
+        episodes.sort_by_key(|episode| episode.seed);
+        Self { episodes }
+    }
+
+    /// The episodes, ordered by seed.
+    pub fn episodes(&self) -> &[EpisodeReport] {
+        &self.episodes
+    }
+
+    /// Share of the episodes ending as `outcome` says.
+    fn rate(&self, outcome: impl Fn(&EpisodeReport) -> bool) -> f64 {
+        match self.episodes.len() {
+            0 => 0.0,
+            n => self.episodes.iter().filter(|e| outcome(e)).count() as f64 / n as f64,
+        }
+    }
+
+    /// Share of the episodes where the agent escaped with gold.
+    pub fn win_rate(&self) -> f64 {
+        self.rate(EpisodeReport::is_win)
+    }
+
+    /// Share of the episodes ending with `outcome`, as a death by
+    /// some cause.
+    pub fn outcome_rate(&self, outcome: &Outcome) -> f64 {
+        self.rate(|e| e.outcome == *outcome)
+    }
+
+    fn mean(&self, value: impl Fn(&EpisodeReport) -> f64) -> f64 {
+        match self.episodes.len() {
+            0 => 0.0,
+            n => self.episodes.iter().map(value).sum::<f64>() / n as f64,
+        }
+    }
+
+    /// Mean score over the episodes.
+    pub fn mean_score(&self) -> f64 {
+        self.mean(|e| e.score as f64)
+    }
+
+    /// Median score over the episodes.
+    pub fn median_score(&self) -> f64 {
+        let mut scores: Vec<isize> = self.episodes.iter().map(|e| e.score).collect();
+        scores.sort();
+        match scores.len() {
+            0 => 0.0,
+            n if n % 2 == 0 => (scores[n / 2 - 1] + scores[n / 2]) as f64 / 2.0,
+            n => scores[n / 2] as f64,
+        }
+    }
+
+    /// Half-width of the 95% confidence interval of the mean
+    /// score, under a normal approximation.
+    pub fn score_margin(&self) -> f64 {
+        let n = self.episodes.len() as f64;
+        if n < 2.0 {
+            return 0.0;
+        }
+        let mean = self.mean_score();
+        let variance = self
+            .episodes
+            .iter()
+            .map(|e| (e.score as f64 - mean).powi(2))
+            .sum::<f64>()
+            / (n - 1.0);
+        Z_95 * (variance / n).sqrt()
+    }
+
+    /// Half-width of the 95% confidence interval of the win rate,
+    /// under a normal approximation.
+    pub fn win_rate_margin(&self) -> f64 {
+        let n = self.episodes.len() as f64;
+        if n == 0.0 {
+            return 0.0;
+        }
+        let rate = self.win_rate();
+        Z_95 * (rate * (1.0 - rate) / n).sqrt()
+    }
+
+    /// Mean number of steps per episode.
+    pub fn mean_steps(&self) -> f64 {
+        self.mean(|e| e.steps as f64)
+    }
+
+    /// Mean time the agent spent choosing an action.
+    pub fn mean_inference(&self) -> Duration {
+        let steps: usize = self.episodes.iter().map(|e| e.steps).sum();
+        let total: Duration = self.episodes.iter().map(|e| e.inference).sum();
+        match steps {
+            0 => Duration::ZERO,
+            steps => total / steps as u32,
+        }
+    }
+
+    /// Writes one CSV row per episode, after a header row.
+    pub fn write_csv(&self, out: &mut impl Write) -> std::io::Result<()> {
+        writeln!(out, "{}", CSV_HEADER)?;
+        for episode in &self.episodes {
+            episode.write_csv(out)?;
+        }
+        Ok(())
+    }
+
+    /// Writes the statistics of the benchmark as a JSON object on
+    /// its own line.
+    pub fn write_json(&self, out: &mut impl Write) -> std::io::Result<()> {
+        writeln!(
+            out,
+            "{{\"episodes\":{},\"win_rate\":{:.4},\"pit_rate\":{:.4},\"wumpus_rate\":{:.4},\
+             \"empty_handed_rate\":{:.4},\"truncated_rate\":{:.4},\"looped_rate\":{:.4},\
+             \"mean_score\":{:.2},\"median_score\":{:.1},\"mean_steps\":{:.2},\
+             \"mean_inference_ms\":{:.3}}}",
+            self.episodes.len(),
+            self.win_rate(),
+            self.outcome_rate(&Outcome::FellIntoPit),
+            self.outcome_rate(&Outcome::EatenByWumpus),
+            self.outcome_rate(&Outcome::EmptyHanded),
+            self.outcome_rate(&Outcome::Truncated(Truncation::StepLimit)),
+            self.outcome_rate(&Outcome::Truncated(Truncation::RepeatedState)),
+            self.mean_score(),
+            self.median_score(),
+            self.mean_steps(),
+            self.mean_inference().as_secs_f64() * 1000.0
+        )
+    }
+}
+
+/// Names `outcome` with a single word, for CSV output.
+pub fn outcome_label(outcome: &Outcome) -> &'static str {
+    match outcome {
+        Outcome::Escaped { .. } => "escaped",
+        Outcome::EmptyHanded => "empty-handed",
+        Outcome::FellIntoPit => "pit",
+        Outcome::EatenByWumpus => "wumpus",
+        Outcome::Truncated(Truncation::StepLimit) => "truncated",
+        Outcome::Truncated(Truncation::RepeatedState) => "looped",
+    }
+}
+
+/// Runs the agent of `config` over its worlds, spreading them
+/// over its threads, and logs every step to `log` when given.
+/// Fails on the first episode that can't be played through.
+pub fn run(config: &BenchConfig, log: Option<EventLog>) -> Result<BenchReport, Error> {
+    let next = AtomicUsize::new(0);
+    let log = log.map(Mutex::new);
+    let results: Mutex<Vec<Result<EpisodeReport, Error>>> = Mutex::new(Vec::new());
+
+    std::thread::scope(|scope| {
+        for _ in 0..config.threads.max(1) {
+            scope.spawn(|| {
+                // Kept from one episode to the next, for agents that
+                // can be reused
+                let mut agent = None;
+                loop {
+                    let index = next.fetch_add(1, Ordering::Relaxed);
+                    if index >= config.episodes {
+                        break;
+                    }
+                    let result = run_episode(config, index, &mut agent, log.as_ref());
+                    let failed = result.is_err();
+                    results.lock().unwrap().push(result);
+                    if failed {
+                        // Stop handing out episodes to every thread
+                        next.store(config.episodes, Ordering::Relaxed);
+                    }
+                }
+            });
+        }
+    });
+
+    let episodes = results
+        .into_inner()
+        .unwrap()
+        .into_iter()
+        .collect::<Result<Vec<_>, _>>()?;
+    Ok(BenchReport::new(episodes))
+}
+
+/// Plays the episode of index `index` of the benchmark, with the
+/// agent `previous` played the last episode with when it can be
+/// reused, leaving the agent there for the next one.
+fn run_episode(
+    config: &BenchConfig,
+    index: usize,
+    previous: &mut Option<Box<dyn Agent>>,
+    log: Option<&Mutex<EventLog>>,
+) -> Result<EpisodeReport, Error> {
+    let seed = config.first_seed + index as u64;
+    let mut env = Environment::generate(&config.world, seed)?
+        .with_ruleset(config.ruleset)
+        .with_scoring(config.scoring.clone());
+    let mut agent = match previous
+        .take()
+        .and_then(|mut agent| agent.reuse_for(&env).then_some(agent))
+    {
+        Some(agent) => agent,
+        None => config.agent.build(&env)?,
+    };
+    env.reset(None)?;
+
+    let report = play_episode(&mut env, agent.as_mut(), |env, _, step| {
+        if let Some(log) = log {
+            let mut log = log.lock().unwrap();
+            log.set_episode(index + 1);
+            log.record(env, &step.action, &step.result.info, step.kb)?;
+        }
+        Ok(())
+    })?;
+    *previous = Some(agent);
+    Ok(report)
+}
+
+/// A step of an episode played by `play_episode`.
+pub struct EpisodeStep {
+    /// The action the agent took
+    pub action: Action,
+    /// What taking it led to
+    pub result: StepResult,
+    /// The knowledge base queries made to choose the action, for
+    /// agents keeping a knowledge base
+    pub kb: Option<KbStats>,
+}
+
+/// Lets `agent` play the episode `env` was just reset to, until
+/// it ends, and reports how it went. `on_step` is called after
+/// each step, once the agent was told its result, and can stop
+/// the episode by failing. Fails as well when the agent can't
+/// choose an action or the environment can't take it.
+pub fn play_episode(
+    env: &mut Environment,
+    agent: &mut dyn Agent,
+    mut on_step: impl FnMut(&Environment, &mut dyn Agent, &EpisodeStep) -> Result<(), Error>,
+) -> Result<EpisodeReport, Error> {
+    let mut observation = env.observation().clone();
+    agent.on_episode_start(&observation);
+
+    let mut inference = Duration::ZERO;
+    loop {
+        let kb_before = agent.kb_stats();
+        let start = Instant::now();
+        let action = agent.act(&observation)?;
+        inference += start.elapsed();
+
+        let result = env.step_with_reward(&action)?;
+        agent.on_result(&action, &result.info, result.reward);
+        let kb = agent
+            .kb_stats()
+            .zip(kb_before)
+            .map(|(now, before)| now.since(&before));
+        let done = result.terminated || result.truncated;
+        let step = EpisodeStep { action, result, kb };
+        on_step(env, agent, &step)?;
+        if done {
+            break;
+        }
+        observation = step.result.observation;
+    }
+    agent.on_episode_end(env.score());
+
+    Ok(EpisodeReport::new(env, inference))
+}
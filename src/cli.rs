@@ -0,0 +1,499 @@
+use std::{fmt::Display, time::Duration};
+
+use wumpus_world::{
+    agents::AgentKind,
+    env::Ruleset,
+    external::{self, DEFAULT_TIMEOUT},
+};
+
+pub const USAGE: &str = "\
+Usage: wumpus-world [OPTIONS]
+
+Without options, the game starts at its main menu.
+
+Options:
+  -m, --mode <MODE>        Launch straight into a mode: play, agent, bench, arena,
+                           replay
+  -g, --grid <GRID>        Grid variant: classic, random [default: random]
+      --map <PATH>         Play on the world of a map file
+  -s, --seed <N>           World seed, or first seed of a bench or arena
+  -a, --agent <AGENT>      Agent to run: kb, kb-unknown, mcts, external [default: kb]
+      --iterations <N>     Simulations per step of the mcts agent
+      --command <COMMAND>  Program playing the external agent, also entered in arenas
+      --timeout <MS>       Time the external agent has to reply [default: 1000]
+  -r, --ruleset <RULESET>  Ruleset to play by: absolute, aima [default: absolute]
+      --max-steps <N>      Cut episodes short after this many steps [default: 25
+                           per room for agent, bench and arena runs]
+      --max-repeats <N>    Cut episodes short once the agent is back in the same
+                           state more than this many times
+  -d, --delay <MS>         Pause between the steps of an agent [default: 0]
+  -f, --format <FORMAT>    Output of agent, bench and arena runs: text, json, csv
+                           [default: text]
+  -n, --episodes <N>       Episodes of a bench or arena [default: 100]
+  -t, --threads <N>        Threads of a bench or arena [default: 1]
+      --log <PATH>         Log every step as JSON lines to a file
+      --replay <PATH>      Replay file to watch
+  -h, --help               Print this help";
+
+/// What to launch straight into.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Mode {
+    Play,
+    Agent,
+    Bench,
+    Arena,
+    Replay,
+}
+
+/// Where the world comes from.
+#[derive(Debug, Clone, PartialEq)]
+pub enum World {
+    Classic,
+    Random,
+    Map(String),
+}
+
+/// How agent, bench and arena runs report their results.
+///
+/// ## Variants
+/// * `Text` - The usual board and summary display
+/// * `Json` - One JSON object per line, without the board
+/// * `Csv` - One CSV row per episode, without the board
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub enum Format {
+    #[default]
+    Text,
+    Json,
+    Csv,
+}
+
+/// Options taking a value, which is every option but `--help`.
+const OPTIONS: &[&str] = &[
+    "-m",
+    "--mode",
+    "-g",
+    "--grid",
+    "--map",
+    "-s",
+    "--seed",
+    "-a",
+    "--agent",
+    "--iterations",
+    "--command",
+    "--timeout",
+    "-r",
+    "--ruleset",
+    "--max-steps",
+    "--max-repeats",
+    "-d",
+    "--delay",
+    "-f",
+    "--format",
+    "-n",
+    "--episodes",
+    "-t",
+    "--threads",
+    "--log",
+    "--replay",
+];
+
+/// The options the game was launched with.
+#[derive(Debug, Clone)]
+pub struct Args {
+    /// Mode to launch straight into, or `None` for the main menu
+    pub mode: Option<Mode>,
+    pub world: World,
+    pub seed: Option<u64>,
+    pub agent: AgentKind,
+    /// External agent given with `--command`, if any
+    pub external: Option<AgentKind>,
+    pub ruleset: Ruleset,
+    pub max_steps: Option<usize>,
+    pub max_repeats: Option<usize>,
+    pub delay: Duration,
+    pub format: Format,
+    pub episodes: usize,
+    pub threads: usize,
+    pub log: Option<String>,
+    pub replay: Option<String>,
+    pub help: bool,
+}
+
+impl Default for Args {
+    fn default() -> Self {
+        Self {
+            mode: None,
+            world: World::Random,
+            seed: None,
+            agent: AgentKind::KnowledgeBased,
+            external: None,
+            ruleset: Ruleset::Absolute,
+            max_steps: None,
+            max_repeats: None,
+            delay: Duration::ZERO,
+            format: Format::Text,
+            episodes: 100,
+            threads: 1,
+            log: None,
+            replay: None,
+            help: false,
+        }
+    }
+}
+
+/// Errors raised by malformed command line arguments.
+#[derive(Debug)]
+pub enum ArgError {
+    /// An option the game doesn't know of
+    Unknown(String),
+    /// An option given without its value
+    MissingValue(String),
+    /// An option given a value it doesn't take
+    InvalidValue { option: String, value: String },
+    /// Options that don't make sense together
+    Conflict(&'static str),
+}
+
+impl Display for ArgError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ArgError::Unknown(option) => f.write_fmt(format_args!("unknown option '{}'", option)),
+            ArgError::MissingValue(option) => {
+                f.write_fmt(format_args!("option '{}' needs a value", option))
+            }
+            ArgError::InvalidValue { option, value } => f.write_fmt(format_args!(
+                "invalid value '{}' for option '{}'",
+                value, option
+            )),
+            ArgError::Conflict(reason) => f.write_str(reason),
+        }
+    }
+}
+
+impl std::error::Error for ArgError {}
+
+/// Parses the command line arguments, without the program name.
+pub fn parse(args: impl IntoIterator<Item = String>) -> Result<Args, ArgError> {
+    let mut parsed = Args::default();
+    let mut agent = None;
+    let mut iterations = None;
+    let mut command = None;
+    let mut timeout = None;
+    let mut ruleset = None;
+    let mut options = false;
+
+    let mut args = args.into_iter();
+    while let Some(option) = args.next() {
+        if matches!(option.as_str(), "-h" | "--help") {
+            parsed.help = true;
+            continue;
+        }
+        if !OPTIONS.contains(&option.as_str()) {
+            return Err(ArgError::Unknown(option));
+        }
+        let value = args
+            .next()
+            .ok_or_else(|| ArgError::MissingValue(option.clone()))?;
+        let invalid = || ArgError::InvalidValue {
+            option: option.clone(),
+            value: value.clone(),
+        };
+
+        match option.as_str() {
+            "-m" | "--mode" => {
+                parsed.mode = Some(match value.as_str() {
+                    "play" => Mode::Play,
+                    "agent" => Mode::Agent,
+                    "bench" => Mode::Bench,
+                    "arena" => Mode::Arena,
+                    "replay" => Mode::Replay,
+                    _ => return Err(invalid()),
+                });
+                continue;
+            }
+            "-g" | "--grid" => {
+                parsed.world = match value.as_str() {
+                    "classic" => World::Classic,
+                    "random" => World::Random,
+                    _ => return Err(invalid()),
+                }
+            }
+            "--map" => parsed.world = World::Map(value),
+            "-s" | "--seed" => parsed.seed = Some(value.parse().map_err(|_| invalid())?),
+            "-a" | "--agent" => {
+                agent = Some(
+                    AgentKind::registered()
+                        .into_iter()
+                        .chain([AgentKind::External {
+                            program: String::new(),
+                            args: Vec::new(),
+                            timeout: DEFAULT_TIMEOUT,
+                        }])
+                        .find(|agent| agent.name() == value)
+                        .ok_or_else(invalid)?,
+                )
+            }
+            "--command" => command = Some(external::split_command(&value).map_err(|_| invalid())?),
+            "--timeout" => {
+                timeout = Some(Duration::from_millis(value.parse().map_err(|_| invalid())?))
+            }
+            "--iterations" => iterations = Some(value.parse().map_err(|_| invalid())?),
+            "-r" | "--ruleset" => {
+                ruleset = Some(match value.as_str() {
+                    "absolute" => Ruleset::Absolute,
+                    "aima" => Ruleset::Aima,
+                    _ => return Err(invalid()),
+                })
+            }
+            "--max-steps" => parsed.max_steps = Some(value.parse().map_err(|_| invalid())?),
+            "--max-repeats" => parsed.max_repeats = Some(value.parse().map_err(|_| invalid())?),
+            "-d" | "--delay" => {
+                parsed.delay = Duration::from_millis(value.parse().map_err(|_| invalid())?)
+            }
+            "-f" | "--format" => {
+                parsed.format = match value.as_str() {
+                    "text" => Format::Text,
+                    "json" => Format::Json,
+                    "csv" => Format::Csv,
+                    _ => return Err(invalid()),
+                }
+            }
+            "-n" | "--episodes" => parsed.episodes = value.parse().map_err(|_| invalid())?,
+            "-t" | "--threads" => parsed.threads = value.parse().map_err(|_| invalid())?,
+            "--log" => parsed.log = Some(value),
+            "--replay" => parsed.replay = Some(value),
+            _ => unreachable!("every option is handled"),
+        }
+        options = true;
+    }
+
+    if let Some(iterations) = iterations {
+        match agent {
+            Some(AgentKind::MonteCarlo { .. }) => {}
+            _ => {
+                return Err(ArgError::Conflict(
+                    "--iterations only applies to --agent mcts",
+                ));
+            }
+        }
+        agent = Some(AgentKind::MonteCarlo { iterations });
+    }
+    if let Some((program, args)) = command {
+        parsed.external = Some(AgentKind::External {
+            program,
+            args,
+            timeout: timeout.unwrap_or(DEFAULT_TIMEOUT),
+        });
+    } else if timeout.is_some() {
+        return Err(ArgError::Conflict(
+            "--timeout only applies with a --command",
+        ));
+    }
+    let external = matches!(agent, Some(AgentKind::External { .. }));
+    if external {
+        agent = Some(
+            parsed
+                .external
+                .clone()
+                .ok_or(ArgError::Conflict("--agent external needs a --command"))?,
+        );
+    }
+    parsed.agent = agent.unwrap_or(parsed.agent);
+    parsed.ruleset = ruleset.unwrap_or(parsed.ruleset);
+
+    match parsed.mode {
+        None if options => return Err(ArgError::Conflict("options need a --mode to apply to")),
+        Some(Mode::Agent | Mode::Bench) if !parsed.agent.supports(parsed.ruleset) => {
+            return Err(ArgError::Conflict(
+                "only external agents play by the aima ruleset",
+            ));
+        }
+        Some(Mode::Arena) if parsed.ruleset != Ruleset::Absolute && parsed.external.is_none() => {
+            return Err(ArgError::Conflict(
+                "arenas by the aima ruleset need an external agent's --command",
+            ));
+        }
+        Some(Mode::Bench | Mode::Arena) if parsed.world != World::Random => {
+            return Err(ArgError::Conflict(
+                "benchmarks and arenas only run on random grids",
+            ));
+        }
+        Some(mode) if mode != Mode::Arena && parsed.external.is_some() && !external => {
+            return Err(ArgError::Conflict(
+                "--command only applies to --agent external, or to arenas",
+            ));
+        }
+        Some(Mode::Replay) if parsed.replay.is_none() => {
+            return Err(ArgError::Conflict("--mode replay needs a --replay file"));
+        }
+        _ => {}
+    }
+    if parsed.world != World::Random && parsed.seed.is_some() {
+        return Err(ArgError::Conflict("--seed only applies to random grids"));
+    }
+    Ok(parsed)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn args(line: &str) -> Result<Args, ArgError> {
+        parse(line.split_whitespace().map(str::to_string))
+    }
+
+    fn conflict(line: &str) -> &'static str {
+        match args(line) {
+            Err(ArgError::Conflict(reason)) => reason,
+            other => panic!("expected a conflict for `{}`, got {:?}", line, other),
+        }
+    }
+
+    #[test]
+    fn defaults_to_the_main_menu() {
+        let parsed = args("").unwrap();
+        assert_eq!(parsed.mode, None);
+        assert_eq!(parsed.world, World::Random);
+        assert_eq!(parsed.agent, AgentKind::KnowledgeBased);
+        assert!(!parsed.help);
+    }
+
+    #[test]
+    fn parses_short_and_long_options() {
+        let parsed = args("-m bench -a kb-unknown -s 7 -n 20 -t 4 -f csv --max-steps 300").unwrap();
+        assert_eq!(parsed.mode, Some(Mode::Bench));
+        assert_eq!(parsed.agent, AgentKind::KnowledgeBasedUnknownSize);
+        assert_eq!(parsed.seed, Some(7));
+        assert_eq!(parsed.episodes, 20);
+        assert_eq!(parsed.threads, 4);
+        assert_eq!(parsed.format, Format::Csv);
+        assert_eq!(parsed.max_steps, Some(300));
+
+        let parsed = args("--mode play --grid classic --delay 50").unwrap();
+        assert_eq!(parsed.mode, Some(Mode::Play));
+        assert_eq!(parsed.world, World::Classic);
+        assert_eq!(parsed.delay, Duration::from_millis(50));
+    }
+
+    #[test]
+    fn parses_agent_settings() {
+        let parsed = args("-m agent -a mcts --iterations 40").unwrap();
+        assert_eq!(parsed.agent, AgentKind::MonteCarlo { iterations: 40 });
+
+        let parsed = args("-m agent -a external --command ./bot --timeout 250 -r aima").unwrap();
+        let external = AgentKind::External {
+            program: "./bot".to_string(),
+            args: Vec::new(),
+            timeout: Duration::from_millis(250),
+        };
+        assert_eq!(parsed.agent, external);
+        assert_eq!(parsed.external, Some(external));
+        assert_eq!(parsed.ruleset, Ruleset::Aima);
+
+        let line = ["-m", "arena", "--command", "python3 '/my bots/agent.py' -v"];
+        let parsed = parse(line.map(str::to_string)).unwrap();
+        assert_eq!(
+            parsed.external,
+            Some(AgentKind::External {
+                program: "python3".to_string(),
+                args: vec!["/my bots/agent.py".to_string(), "-v".to_string()],
+                timeout: DEFAULT_TIMEOUT,
+            })
+        );
+        let line = ["-m", "arena", "--command", "python3 'agent.py"];
+        assert!(matches!(
+            parse(line.map(str::to_string)),
+            Err(ArgError::InvalidValue { option, .. }) if option == "--command"
+        ));
+    }
+
+    #[test]
+    fn parses_help_anywhere() {
+        assert!(args("--help").unwrap().help);
+        assert!(args("-m play -h").unwrap().help);
+    }
+
+    #[test]
+    fn rejects_unknown_options() {
+        assert!(matches!(args("--fast"), Err(ArgError::Unknown(option)) if option == "--fast"));
+    }
+
+    #[test]
+    fn rejects_options_without_a_value() {
+        assert!(matches!(args("-m"), Err(ArgError::MissingValue(option)) if option == "-m"));
+    }
+
+    #[test]
+    fn rejects_invalid_values() {
+        for line in [
+            "-m watch",
+            "-g huge",
+            "-s -1",
+            "-a genius",
+            "--timeout soon",
+            "--iterations many",
+            "-r chess",
+            "--max-steps 1.5",
+            "--max-repeats x",
+            "-d later",
+            "-f xml",
+            "-n all",
+            "-t -2",
+        ] {
+            let (option, value) = line.split_once(' ').unwrap();
+            assert!(
+                matches!(
+                    args(line),
+                    Err(ArgError::InvalidValue { option: o, value: v }) if o == option && v == value
+                ),
+                "{}",
+                line
+            );
+        }
+    }
+
+    #[test]
+    fn rejects_conflicting_options() {
+        assert_eq!(
+            conflict("-m agent --iterations 10"),
+            "--iterations only applies to --agent mcts"
+        );
+        assert_eq!(
+            conflict("-m agent --timeout 10"),
+            "--timeout only applies with a --command"
+        );
+        assert_eq!(
+            conflict("-m agent -a external"),
+            "--agent external needs a --command"
+        );
+        assert_eq!(conflict("-g classic"), "options need a --mode to apply to");
+        assert_eq!(
+            conflict("-m agent -r aima"),
+            "only external agents play by the aima ruleset"
+        );
+        assert_eq!(
+            conflict("-m arena -r aima"),
+            "arenas by the aima ruleset need an external agent's --command"
+        );
+        assert_eq!(
+            conflict("-m bench -g classic"),
+            "benchmarks and arenas only run on random grids"
+        );
+        assert_eq!(
+            conflict("-m agent --command ./bot"),
+            "--command only applies to --agent external, or to arenas"
+        );
+        assert_eq!(conflict("-m replay"), "--mode replay needs a --replay file");
+        assert_eq!(
+            conflict("-m play -g classic -s 3"),
+            "--seed only applies to random grids"
+        );
+    }
+
+    #[test]
+    fn enters_external_agents_in_arenas() {
+        let parsed = args("-m arena --command ./bot").unwrap();
+        assert_eq!(parsed.agent, AgentKind::KnowledgeBased);
+        assert!(parsed.external.is_some());
+    }
+}
@@ -1,59 +1,230 @@
 use std::{
-    collections::HashSet,
+    collections::{HashMap, HashSet},
     fmt::{Display, Write},
 };
 
-use rand::seq::IteratorRandom;
-
 use crate::{
     agent::{Action, Direction, Observation},
+    error::Error,
     grid::{Grid, Pos},
     room::{Room, RoomKind},
+    scoring::{GoldReward, ScoringRules},
+    world::WorldConfig,
 };
 
-const MOVE_PENALTY: usize = 1;
-const ARROW_PENALTY: usize = 10;
-const PIT_PENALTY: usize = 1000;
-const WUMPUS_PENALTY: usize = 1000;
-const GOLD_REWARD: usize = 1000;
+/// Arrows the agent starts with
+const ARROWS: usize = 1;
+/// Direction the agent faces at the start, under the AIMA ruleset
+const INITIAL_ORIENTATION: Direction = Direction::East;
 
+/// What the agent can sense in a room.
 #[derive(Debug, Eq, Hash, PartialEq, Clone)]
 pub enum Sense {
+    /// The Wumpus is in a neighboring room
     Stench,
+    /// A pit is in a neighboring room
     Breeze,
-    Scream(Direction),
+    /// The Wumpus was killed by an arrow
+    Scream,
+    /// Gold is in the room
     Glitter,
+    /// The agent ran into the wall towards the direction
     Bump(Direction),
+    /// The agent tried to climb out away from the entrance
     Ceil,
 }
 
-#[derive(Debug)]
+/// What the agent can pick up.
+#[derive(Debug, Clone, PartialEq, Eq)]
 pub enum Item {
+    /// A piece of gold
     Gold,
 }
 
-#[derive(Debug)]
+/// What taking an action led to.
+#[derive(Debug, Clone, PartialEq, Eq)]
 pub enum ActionResult {
+    /// The action went through
     Ok,
+    /// The episode is over
     GameOver,
+    /// The action made the agent sense something
     Sense(Sense),
+    /// The agent picked up the item
     Grabbed(Item),
+    /// Shooting with no arrow left
+    OutOfArrows,
+}
+
+/// What taking an action led to, in the shape of a Gym step.
+#[derive(Debug)]
+pub struct StepResult {
+    /// What the agent observes after the action
+    pub observation: Observation,
+    /// The score gained (or lost) by the action
+    pub reward: isize,
+    /// Whether the episode ended by climbing out or dying
+    pub terminated: bool,
+    /// Whether the episode was cut short by the step limit
+    pub truncated: bool,
+    /// The `ActionResult` of the action
+    pub info: ActionResult,
+}
+
+/// How an episode ended.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Outcome {
+    /// Climbed out carrying this many gold pieces
+    Escaped {
+        /// Gold pieces carried out
+        gold: usize,
+    },
+    /// Climbed out without any gold
+    EmptyHanded,
+    /// Fell into a pit
+    FellIntoPit,
+    /// Was eaten by the Wumpus
+    EatenByWumpus,
+    /// Was cut short before the episode ended
+    Truncated(Truncation),
+}
+
+/// Why an episode was cut short.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Truncation {
+    /// The agent ran out of steps
+    StepLimit,
+    /// The agent came back to the same state more times than
+    /// allowed, going around in circles
+    RepeatedState,
 }
 
+impl Display for Outcome {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Outcome::Escaped { gold } => f.write_fmt(format_args!(
+                "Escaped with {} gold piece{}",
+                gold,
+                if *gold == 1 { "" } else { "s" }
+            )),
+            Outcome::EmptyHanded => f.write_str("Climbed out empty-handed"),
+            Outcome::FellIntoPit => f.write_str("Died falling into a pit"),
+            Outcome::EatenByWumpus => f.write_str("Died eaten by the Wumpus"),
+            Outcome::Truncated(Truncation::StepLimit) => f.write_str("Ran out of steps"),
+            Outcome::Truncated(Truncation::RepeatedState) => {
+                f.write_str("Cut short going around in circles")
+            }
+        }
+    }
+}
+
+/// The rules the environment plays by.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Ruleset {
+    /// The agent picks a direction on each move or shot
+    #[default]
+    Absolute,
+    /// The textbook rules: the agent faces a direction, starting
+    /// East, and turns with `TurnLeft` and `TurnRight`. `Bump`
+    /// and `Scream` are only perceived right after the action
+    /// causing them. Meant to be scored with `ScoringRules::aima`.
+    Aima,
+}
+
+impl Ruleset {
+    /// Whether `action` is part of the ruleset.
+    pub fn allows(&self, action: &Action) -> bool {
+        match action {
+            Action::Climb | Action::Grab => true,
+            Action::Move(_) | Action::Shoot(_) => *self == Ruleset::Absolute,
+            Action::Forward | Action::TurnLeft | Action::TurnRight | Action::ShootForward => {
+                *self == Ruleset::Aima
+            }
+        }
+    }
+}
+
+/// The Wumpus World an agent plays in, along with the agent's
+/// position, score and inventory.
+#[derive(Clone)]
 pub struct Environment {
     grid: Grid,
+    /// The world as it was laid out, restored on `initialize`
+    layout: Grid,
     score: isize,
     init_pos: Pos,
     agent_pos: Pos,
+    /// Direction the agent faces, under the AIMA ruleset
+    orientation: Direction,
+    arrows: usize,
+    /// Gold pieces carried by the agent
+    gold: usize,
+    ruleset: Ruleset,
+    scoring: ScoringRules,
+    /// Number of steps taken in the episode
+    steps: usize,
+    /// How the episode ended, once it is over
+    outcome: Option<Outcome>,
     curr_obs: Observation,
+    /// Seed the world was generated from, if it was generated
+    seed: Option<u64>,
+    /// Config the world was generated from, if it was generated
+    config: Option<WorldConfig>,
+    /// Header of the map the world was loaded from, if any
+    metadata: Vec<(String, String)>,
+    /// Times each state was reached in the episode, only kept
+    /// under a repeat limit
+    visits: HashMap<AgentState, usize>,
 }
 
+/// What tells apart the states the agent can come back to: its
+/// position, orientation, arrows, gold and how many rooms it has
+/// visited. The rest of the world only changes along with these,
+/// so an agent still exploring never repeats a state.
+type AgentState = (Pos, Direction, usize, usize, usize);
+
+/// The state of an episode at some point, taken with
+/// `Environment::snapshot` and brought back with `restore`.
+///
+/// It holds the world the episode is played in (its layout,
+/// start position, seed and map header) and what changes while
+/// playing (the rooms and their visited flags, the agent's
+/// position, orientation, score and inventory), but not the
+/// rules, which only belong to the environment it is restored
+/// into.
+#[derive(Clone, Debug)]
+pub struct Snapshot {
+    layout: Grid,
+    init_pos: Pos,
+    seed: Option<u64>,
+    config: Option<WorldConfig>,
+    metadata: Vec<(String, String)>,
+    grid: Grid,
+    score: isize,
+    agent_pos: Pos,
+    orientation: Direction,
+    arrows: usize,
+    gold: usize,
+    steps: usize,
+    outcome: Option<Outcome>,
+    curr_obs: Observation,
+    visits: HashMap<AgentState, usize>,
+}
+
+/// The world an `Environment` starts with.
 pub enum GridType {
+    /// The 4x4 world of the textbook
     Classic,
+    /// A random world generated from a fresh seed
     Random,
+    /// A random world generated deterministically from a seed, so
+    /// the same seed always yields the same world
+    Seeded(u64),
 }
 
 impl Environment {
+    /// An environment on a world of `grid_type`, under the
+    /// absolute ruleset and the default scoring.
     pub fn new(grid_type: GridType) -> Self {
         let mut grid: Grid;
         let init_pos: Pos;
@@ -83,75 +254,51 @@ impl Environment {
                 grid[3][1].set_kind(RoomKind::Void);
                 grid[3][2].set_kind(RoomKind::Pit);
                 grid[3][3].set_kind(RoomKind::Void);
-            }
-            GridType::Random => {
-                const MAX_NROWS: usize = 6;
-                const MAX_NCOLS: usize = 6;
-                const MIN_NROWS: usize = 4;
-                const MIN_NCOLS: usize = 4;
-                const PIT_PROB: f64 = 0.2;
-
-                let nrows: usize = rand::random_range(MIN_NROWS..=MAX_NROWS);
-                let ncols: usize = rand::random_range(MIN_NCOLS..=MAX_NCOLS);
-
-                grid = Grid::new(nrows, ncols);
-
-                /* Initialize all rooms with type `RoomKind::Void` */
-                // cp_set: Candidate Positions Set
-                let mut cp_set: HashSet<Pos> = HashSet::new();
-                for i in 0..nrows {
-                    for j in 0..ncols {
-                        cp_set.insert(Pos::new(i, j));
-                        grid[i][j].set_kind(RoomKind::Void);
-                    }
-                }
 
-                let mut rng = rand::rng();
-                /* Choose a random start position */
-                init_pos = cp_set.iter().choose(&mut rng).unwrap().clone();
-                cp_set.remove(&init_pos);
-
-                cp_set.remove(&Pos::new(
-                    (init_pos.row as isize - 1).max(0) as usize,
-                    init_pos.col,
-                ));
-                cp_set.remove(&Pos::new(init_pos.row + 1, init_pos.col));
-                cp_set.remove(&Pos::new(
-                    init_pos.row,
-                    (init_pos.col as isize - 1).max(0) as usize,
-                ));
-                cp_set.remove(&Pos::new(init_pos.row, init_pos.col + 1));
-
-                /* Choose random positions that are not the start position
-                to put `RoomKind::Pit` rooms with probability `PIT_PROB` */
-                let mut pits: HashSet<Pos> = HashSet::new();
-                for pos in cp_set.iter() {
-                    if rand::random_bool(PIT_PROB) {
-                        grid.mut_room_at(&pos).set_kind(RoomKind::Pit);
-                        pits.insert(pos.clone());
-                    }
-                }
-                cp_set.retain(|pos| !pits.contains(pos));
-
-                /* Choose a random position that is not the start
-                position and doesn't containt a pit to put the Wumpus */
-                let wumpus_pos = cp_set.iter().choose(&mut rng).unwrap();
-                grid.mut_room_at(&wumpus_pos).set_kind(RoomKind::Wumpus);
-
-                /* Choose a random position that is not the start
-                position and contains neither a pit or wumpus
-                to put the Gold! */
-                let gold_pos = cp_set.iter().choose(&mut rng).unwrap();
-                grid.mut_room_at(&gold_pos).set_kind(RoomKind::Gold);
+                debug_assert!(grid.validate(&init_pos).is_ok());
+            }
+            GridType::Random => return Self::new(GridType::Seeded(rand::random())),
+            GridType::Seeded(seed) => {
+                return Self::generate(&WorldConfig::default(), seed)
+                    .expect("default world config is valid");
             }
         }
 
+        Self::from_grid(grid, init_pos)
+    }
+
+    /// Generates a random world from `config`, drawing every
+    /// choice from a generator seeded with `seed`.
+    pub fn generate(config: &WorldConfig, seed: u64) -> Result<Self, Error> {
+        let (grid, init_pos) = config.generate(seed)?;
+        let mut env = Self::from_grid(grid, init_pos);
+        env.seed = Some(seed);
+        env.config = Some(config.clone());
+
+        Ok(env)
+    }
+
+    /// Builds an environment around an already laid out `grid`,
+    /// with the agent starting at `init_pos`.
+    pub fn from_grid(grid: Grid, init_pos: Pos) -> Self {
         let mut env = Environment {
+            layout: grid.clone(),
             grid,
             score: 0,
             init_pos: init_pos.clone(),
             agent_pos: init_pos.clone(),
-            curr_obs: Observation::new(init_pos.clone()),
+            orientation: INITIAL_ORIENTATION,
+            arrows: ARROWS,
+            gold: 0,
+            ruleset: Ruleset::default(),
+            scoring: ScoringRules::default(),
+            steps: 0,
+            outcome: None,
+            curr_obs: Observation::new(init_pos),
+            seed: None,
+            config: None,
+            metadata: Vec::new(),
+            visits: HashMap::new(),
         };
 
         Self::initialize(&mut env);
@@ -159,18 +306,95 @@ impl Environment {
         env
     }
 
+    /// Switches the environment to `ruleset`, and starts the
+    /// episode over.
+    pub fn with_ruleset(mut self, ruleset: Ruleset) -> Self {
+        self.ruleset = ruleset;
+        self.initialize();
+        self
+    }
+
+    /// The rules the environment plays by.
+    pub fn ruleset(&self) -> Ruleset {
+        self.ruleset
+    }
+
+    /// Switches the environment to scoring with `scoring`, and
+    /// starts the episode over.
+    pub fn with_scoring(mut self, scoring: ScoringRules) -> Self {
+        self.scoring = scoring;
+        self.initialize();
+        self
+    }
+
+    /// How actions are scored.
+    pub fn scoring(&self) -> &ScoringRules {
+        &self.scoring
+    }
+
+    /// Number of steps taken in the episode.
+    pub fn steps(&self) -> usize {
+        self.steps
+    }
+
+    /// How the episode ended, or `None` while it goes on.
+    pub fn outcome(&self) -> Option<&Outcome> {
+        self.outcome.as_ref()
+    }
+
+    /// Seed the world was generated from, `None` for other
+    /// worlds.
+    pub fn seed(&self) -> Option<u64> {
+        self.seed
+    }
+
+    /// The world as it is now.
     pub fn grid(&self) -> &Grid {
         &self.grid
     }
 
+    /// The world as it was laid out at the start of the episode,
+    /// before any gold was grabbed or Wumpus killed.
+    pub fn layout(&self) -> &Grid {
+        &self.layout
+    }
+
+    /// Records the header of the map the world was loaded from.
+    pub fn with_metadata(mut self, metadata: Vec<(String, String)>) -> Self {
+        self.metadata = metadata;
+        self
+    }
+
+    /// The `key: value` header of the map the world was loaded
+    /// from, empty for other worlds.
+    pub fn metadata(&self) -> &[(String, String)] {
+        &self.metadata
+    }
+
+    /// Position of the agent.
     pub fn agent_position(&self) -> &Pos {
         &self.agent_pos
     }
 
-    pub fn mut_agent_position(&mut self) -> &mut Pos {
-        &mut self.agent_pos
+    /// Position the agent starts from, and climbs out from.
+    pub fn start_position(&self) -> &Pos {
+        &self.init_pos
+    }
+
+    /// Places the agent at `pos` without charging any move
+    /// penalty, and refreshes the current observation.
+    pub fn set_agent_position(&mut self, pos: Pos) -> Result<(), Error> {
+        if !self.grid.contains(&pos) {
+            return Err(Error::OffGrid(pos));
+        }
+        self.agent_pos = pos;
+        self.lightup_agent_position();
+        self.update_observation();
+        Ok(())
     }
 
+    /// Whether the agent can move towards `direction` without
+    /// bumping into a wall.
     pub fn is_direction_valid(&self, direction: &Direction) -> bool {
         match direction {
             Direction::North => self.agent_pos.row > 0,
@@ -200,132 +424,371 @@ impl Environment {
             .collect()
     }
 
+    /// Arrows the agent has left.
+    pub fn arrows(&self) -> usize {
+        self.arrows
+    }
+
+    /// Gold pieces carried by the agent.
+    pub fn gold(&self) -> usize {
+        self.gold
+    }
+
+    /// Score of the episode so far.
     pub fn score(&self) -> isize {
         self.score
     }
 
+    /// Overrides the score of the episode.
     pub fn set_score(&mut self, score: isize) {
         self.score = score;
     }
 
+    /// The room the agent is in.
     pub fn current_room(&self) -> &Room {
         self.grid.room_at(&self.agent_pos)
     }
 
+    /// Mutable access to the room the agent is in.
     pub fn current_room_mut(&mut self) -> &mut Room {
         self.grid.mut_room_at(&self.agent_pos)
     }
 
+    /// What the agent observes now.
     pub fn observation(&self) -> &Observation {
         &self.curr_obs
     }
 
+    /// Marks the room the agent is in as visited.
     pub fn lightup_agent_position(&mut self) {
         self.grid
             .mut_room_at(&self.agent_position().clone())
             .set_visited(true);
     }
 
+    fn update_observation(&mut self) {
+        self.curr_obs.set_position(self.agent_position().clone());
+
+        let current_senses = self.current_room_senses();
+        self.curr_obs.mut_senses().clear();
+        self.curr_obs.mut_senses().extend(current_senses);
+
+        let available_dirs = self.available_directions();
+        self.curr_obs.mut_directions().clear();
+        self.curr_obs.mut_directions().extend(available_dirs);
+
+        self.curr_obs.set_orientation(match self.ruleset {
+            Ruleset::Absolute => None,
+            Ruleset::Aima => Some(self.orientation.clone()),
+        });
+    }
+
+    /// Resets the episode: the world is restored to its original
+    /// layout, bringing back grabbed gold and killed Wumpuses.
     pub fn initialize(&mut self) {
         self.score = 0;
+        self.arrows = ARROWS;
+        self.gold = 0;
+        self.outcome = None;
+        self.steps = 0;
+        self.orientation = INITIAL_ORIENTATION;
         self.agent_pos = self.init_pos.clone();
-        self.curr_obs.set_position(self.init_pos.clone());
-        self.curr_obs.mut_senses().clear();
-        self.curr_obs.mut_directions().clear();
-        let directions = self.available_directions();
-        self.curr_obs.mut_directions().extend(directions);
+        self.grid = self.layout.clone();
         self.grid.initialize();
         self.lightup_agent_position();
+        self.update_observation();
+        self.visits.clear();
+        self.visit();
+    }
+
+    /// Counts a visit to the current state, when keeping track of
+    /// them, and returns how many times it was reached.
+    fn visit(&mut self) -> usize {
+        if self.scoring.repeat_limit.is_none() {
+            return 0;
+        }
+        let visited = (0..self.grid.nrows())
+            .flat_map(|row| self.grid[row].iter())
+            .filter(|room| room.is_visited())
+            .count();
+        let state = (
+            self.agent_pos.clone(),
+            self.orientation.clone(),
+            self.arrows,
+            self.gold,
+            visited,
+        );
+        let visits = self.visits.entry(state).or_default();
+        *visits += 1;
+        *visits
+    }
+
+    /// Starts a new episode and returns its first observation.
+    /// With a `seed`, a generated world is replaced by the one
+    /// its config generates from `seed`. Fails when given a seed
+    /// for a world that wasn't generated, or when no world
+    /// satisfying the config can be generated from `seed`.
+    pub fn reset(&mut self, seed: Option<u64>) -> Result<Observation, Error> {
+        if let Some(seed) = seed {
+            let config = self.config.as_ref().ok_or(Error::NotGenerated)?;
+            let (grid, init_pos) = config.generate(seed)?;
+            self.layout = grid;
+            self.init_pos = init_pos;
+            self.seed = Some(seed);
+        }
+        self.initialize();
+        Ok(self.curr_obs.clone())
+    }
+
+    /// Takes `action` like `step`, reporting the outcome the way
+    /// Gym environments do. Unlike `step`, a step cutting the
+    /// episode short keeps the result of its action as `info`,
+    /// and is told apart by the `truncated` flag.
+    pub fn step_with_reward(&mut self, action: &Action) -> Result<StepResult, Error> {
+        let score = self.score;
+        let info = self.take(action)?;
+        let truncated = matches!(self.outcome, Some(Outcome::Truncated(_)));
+
+        Ok(StepResult {
+            observation: self.curr_obs.clone(),
+            reward: self.score - score,
+            terminated: self.outcome.is_some() && !truncated,
+            truncated,
+            info,
+        })
+    }
+
+    /// Captures the state of the episode, to come back to it
+    /// later with `restore`.
+    pub fn snapshot(&self) -> Snapshot {
+        Snapshot {
+            layout: self.layout.clone(),
+            init_pos: self.init_pos.clone(),
+            seed: self.seed,
+            config: self.config.clone(),
+            metadata: self.metadata.clone(),
+            grid: self.grid.clone(),
+            score: self.score,
+            agent_pos: self.agent_pos.clone(),
+            orientation: self.orientation.clone(),
+            arrows: self.arrows,
+            gold: self.gold,
+            steps: self.steps,
+            outcome: self.outcome.clone(),
+            curr_obs: self.curr_obs.clone(),
+            visits: self.visits.clone(),
+        }
+    }
+
+    /// Brings the episode back to the world and state of
+    /// `snapshot`, whichever environment it was taken from. The
+    /// ruleset and scoring stay those of this environment.
+    pub fn restore(&mut self, snapshot: &Snapshot) {
+        self.layout.clone_from(&snapshot.layout);
+        self.init_pos.clone_from(&snapshot.init_pos);
+        self.seed = snapshot.seed;
+        self.config.clone_from(&snapshot.config);
+        self.metadata.clone_from(&snapshot.metadata);
+        self.grid.clone_from(&snapshot.grid);
+        self.score = snapshot.score;
+        self.agent_pos.clone_from(&snapshot.agent_pos);
+        self.orientation.clone_from(&snapshot.orientation);
+        self.arrows = snapshot.arrows;
+        self.gold = snapshot.gold;
+        self.steps = snapshot.steps;
+        self.outcome.clone_from(&snapshot.outcome);
+        self.curr_obs.clone_from(&snapshot.curr_obs);
+        self.visits.clone_from(&snapshot.visits);
+    }
+
+    /// Takes `action` under the active ruleset. A step cutting the
+    /// episode short results in `ActionResult::GameOver`. Fails
+    /// when `action` isn't part of the ruleset, or when the
+    /// episode is already over.
+    pub fn step(&mut self, action: &Action) -> Result<ActionResult, Error> {
+        let result = self.take(action)?;
+        Ok(match self.outcome {
+            Some(Outcome::Truncated(_)) => ActionResult::GameOver,
+            _ => result,
+        })
+    }
+
+    /// Takes `action` and returns its result, even when the
+    /// episode is cut short along the way.
+    fn take(&mut self, action: &Action) -> Result<ActionResult, Error> {
+        if !self.ruleset.allows(action) {
+            return Err(Error::ActionNotAllowed {
+                action: action.clone(),
+                ruleset: self.ruleset,
+            });
+        }
+        if self.outcome.is_some() {
+            return Err(Error::EpisodeOver);
+        }
+
+        self.steps += 1;
+        self.set_score(self.score() - self.scoring.action_penalty);
+        let result = match self.ruleset {
+            Ruleset::Absolute => self.step_absolute(action),
+            Ruleset::Aima => self.step_aima(action),
+        };
+
+        // Cut the episode short once out of steps, or once back
+        // in the same state too many times
+        let truncation = match result {
+            ActionResult::GameOver => None,
+            _ if self
+                .scoring
+                .step_limit
+                .is_some_and(|limit| self.steps >= limit) =>
+            {
+                Some(Truncation::StepLimit)
+            }
+            _ => {
+                let visits = self.visit();
+                self.scoring
+                    .repeat_limit
+                    .is_some_and(|limit| visits > limit)
+                    .then_some(Truncation::RepeatedState)
+            }
+        };
+        if let Some(truncation) = truncation {
+            let penalty = match truncation {
+                Truncation::StepLimit => self.scoring.step_limit_penalty,
+                Truncation::RepeatedState => self.scoring.repeat_limit_penalty,
+            };
+            self.set_score(self.score() - penalty);
+            self.outcome = Some(Outcome::Truncated(truncation));
+        }
+        Ok(result)
     }
 
-    pub fn step(&mut self, action: &Action) -> ActionResult {
+    fn step_absolute(&mut self, action: &Action) -> ActionResult {
         match action {
-            Action::Move(direction) => {
-                if !self.is_direction_valid(&direction) {
-                    return ActionResult::Sense(Sense::Bump(direction.clone()));
-                }
-                match direction {
-                    Direction::North => {
-                        self.mut_agent_position().row -= 1;
-                    }
-                    Direction::South => {
-                        self.mut_agent_position().row += 1;
-                    }
-                    Direction::East => {
-                        self.mut_agent_position().col += 1;
-                    }
-                    Direction::West => {
-                        self.mut_agent_position().col -= 1;
-                    }
-                }
-                self.lightup_agent_position();
-                self.set_score(self.score() - MOVE_PENALTY as isize);
+            Action::Move(direction) => match self.grid.neighbor(&self.agent_pos, direction) {
+                Some(next) => self.move_agent(next),
+                None => ActionResult::Sense(Sense::Bump(direction.clone())),
+            },
+            Action::Shoot(direction) => self.shoot(direction),
+            Action::Climb => self.climb(),
+            Action::Grab => self.grab(),
+            _ => unreachable!(),
+        }
+    }
 
-                // update current observation
-                self.curr_obs.set_position(self.agent_position().clone());
+    fn step_aima(&mut self, action: &Action) -> ActionResult {
+        // Bump and Scream only last for the step following them
+        self.update_observation();
 
-                let current_senses = self.current_room_senses();
-                self.curr_obs.mut_senses().clear();
-                self.curr_obs.mut_senses().extend(current_senses);
+        match action {
+            Action::Forward => {
+                let direction = self.orientation.clone();
+                let Some(next) = self.grid.neighbor(&self.agent_pos, &direction) else {
+                    let sense = Sense::Bump(direction);
+                    self.curr_obs.mut_senses().insert(sense.clone());
+                    return ActionResult::Sense(sense);
+                };
+                self.move_agent(next)
+            }
+            Action::TurnLeft => {
+                self.orientation = self.orientation.left();
+                self.update_observation();
+                ActionResult::Ok
+            }
+            Action::TurnRight => {
+                self.orientation = self.orientation.right();
+                self.update_observation();
+                ActionResult::Ok
+            }
+            Action::ShootForward => self.shoot(&self.orientation.clone()),
+            Action::Climb => self.climb(),
+            Action::Grab => self.grab(),
+            _ => unreachable!(),
+        }
+    }
 
-                let available_dirs = self.available_directions();
-                self.curr_obs.mut_directions().clear();
-                self.curr_obs.mut_directions().extend(available_dirs);
+    /// Moves the agent into the neighboring room at `next`, and
+    /// ends the game if the room is deadly.
+    fn move_agent(&mut self, next: Pos) -> ActionResult {
+        self.set_score(self.score() - self.scoring.move_penalty);
+        self.agent_pos = next;
+        self.lightup_agent_position();
 
-                match self.current_room().get_kind() {
-                    RoomKind::Pit => {
-                        self.set_score(self.score() - PIT_PENALTY as isize);
-                        ActionResult::GameOver
-                    }
-                    RoomKind::Wumpus => {
-                        self.set_score(self.score() - WUMPUS_PENALTY as isize);
-                        ActionResult::GameOver
-                    }
-                    _ => ActionResult::Ok,
-                }
+        // update current observation
+        self.update_observation();
+
+        match self.current_room().get_kind() {
+            RoomKind::Pit => {
+                self.set_score(self.score() - self.scoring.pit_penalty);
+                self.outcome = Some(Outcome::FellIntoPit);
+                ActionResult::GameOver
             }
-            Action::Shoot(direction) => {
-                if !self.is_direction_valid(&direction) {
-                    return ActionResult::Sense(Sense::Bump(direction.clone()));
-                }
+            RoomKind::Wumpus => {
+                self.set_score(self.score() - self.scoring.wumpus_penalty);
+                self.outcome = Some(Outcome::EatenByWumpus);
+                ActionResult::GameOver
+            }
+            _ => ActionResult::Ok,
+        }
+    }
 
-                let target_position = self.agent_position() + &direction;
-                match self.grid.room_at(&target_position).get_kind() {
-                    RoomKind::Wumpus => {
-                        self.grid
-                            .mut_room_at(&target_position)
-                            .set_kind(RoomKind::Void);
-                        self.grid.mut_room_at(&target_position).set_visited(true);
-                        self.set_score(self.score() - ARROW_PENALTY as isize);
+    fn shoot(&mut self, direction: &Direction) -> ActionResult {
+        if self.arrows == 0 {
+            return ActionResult::OutOfArrows;
+        }
+        self.arrows -= 1;
+        self.set_score(self.score() - self.scoring.arrow_penalty);
 
-                        // update current observation
-                        let sense = Sense::Scream(direction.clone());
-                        self.curr_obs.mut_senses().insert(sense.clone());
+        // The arrow flies straight until it hits
+        // the Wumpus or a wall
+        let mut arrow_pos = self.agent_pos.clone();
+        while let Some(next_pos) = self.grid.neighbor(&arrow_pos, direction) {
+            arrow_pos = next_pos;
+            if *self.grid.room_at(&arrow_pos).get_kind() == RoomKind::Wumpus {
+                self.grid.mut_room_at(&arrow_pos).set_kind(RoomKind::Void);
+                self.grid.mut_room_at(&arrow_pos).set_visited(true);
+                self.set_score(self.score() + self.scoring.wumpus_kill_bonus);
 
-                        ActionResult::Sense(sense)
-                    }
-                    _ => ActionResult::Ok,
-                }
+                // The scream is heard all over the cave
+                self.curr_obs.mut_senses().insert(Sense::Scream);
+                return ActionResult::Sense(Sense::Scream);
             }
-            Action::Climb => match self.agent_pos == self.init_pos {
-                true => ActionResult::GameOver,
-                false => {
-                    let sense = Sense::Ceil;
-                    self.curr_obs.mut_senses().insert(sense.clone());
-                    ActionResult::Sense(sense)
-                }
-            },
-            Action::Grab => {
-                if *self.current_room().get_kind() == RoomKind::Gold {
-                    self.set_score(self.score() + GOLD_REWARD as isize);
-                    self.current_room_mut().set_kind(RoomKind::Void);
-                    self.current_room_mut().mut_senses().remove(&Sense::Glitter);
-                    self.curr_obs.mut_senses().remove(&Sense::Glitter);
-                    ActionResult::Grabbed(Item::Gold)
-                } else {
-                    ActionResult::Ok
+        }
+        ActionResult::Ok
+    }
+
+    fn climb(&mut self) -> ActionResult {
+        match self.agent_pos == self.init_pos {
+            true => {
+                if self.scoring.gold_reward_on == GoldReward::OnClimb {
+                    self.set_score(self.score() + self.gold as isize * self.scoring.gold_reward);
                 }
+                self.outcome = Some(match self.gold {
+                    0 => Outcome::EmptyHanded,
+                    gold => Outcome::Escaped { gold },
+                });
+                ActionResult::GameOver
+            }
+            false => {
+                let sense = Sense::Ceil;
+                self.curr_obs.mut_senses().insert(sense.clone());
+                ActionResult::Sense(sense)
+            }
+        }
+    }
+
+    fn grab(&mut self) -> ActionResult {
+        if *self.current_room().get_kind() == RoomKind::Gold {
+            self.gold += 1;
+            if self.scoring.gold_reward_on == GoldReward::OnGrab {
+                self.set_score(self.score() + self.scoring.gold_reward);
             }
+            self.current_room_mut().set_kind(RoomKind::Void);
+            self.current_room_mut().mut_senses().remove(&Sense::Glitter);
+            self.curr_obs.mut_senses().remove(&Sense::Glitter);
+            ActionResult::Grabbed(Item::Gold)
+        } else {
+            ActionResult::Ok
         }
     }
 }
@@ -358,7 +821,13 @@ impl Display for Environment {
                             "{}{}{}{}  ",
                             room.get_kind(),
                             match agent_pos.row == i && agent_pos.col == j {
-                                true => "A",
+                                true => match (self.ruleset, &self.orientation) {
+                                    (Ruleset::Absolute, _) => "A",
+                                    (Ruleset::Aima, Direction::North) => "^",
+                                    (Ruleset::Aima, Direction::South) => "v",
+                                    (Ruleset::Aima, Direction::East) => ">",
+                                    (Ruleset::Aima, Direction::West) => "<",
+                                },
                                 false => "_",
                             },
                             match room.has_sense(Sense::Breeze) {
@@ -381,3 +850,344 @@ impl Display for Environment {
         Ok(())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::map::Map;
+
+    /// An environment on the map `text`.
+    fn world(text: &str) -> Environment {
+        Map::parse(text).unwrap().into_environment()
+    }
+
+    #[test]
+    fn shoots_the_wumpus_rooms_away() {
+        let mut env = world("A _ _ W\n_ _ _ G\n");
+        let result = env.step(&Action::Shoot(Direction::East)).unwrap();
+        assert_eq!(result, ActionResult::Sense(Sense::Scream));
+        assert_eq!(env.arrows(), 0);
+        assert_eq!(env.score(), -10);
+        assert_eq!(
+            *env.grid().room_at(&Pos::new(0, 3)).get_kind(),
+            RoomKind::Void
+        );
+        assert!(env.observation().senses().contains(&Sense::Scream));
+
+        // The scream is only heard right after the shot
+        env.step(&Action::Move(Direction::South)).unwrap();
+        assert!(!env.observation().senses().contains(&Sense::Scream));
+
+        let score = env.score();
+        let result = env.step(&Action::Shoot(Direction::East)).unwrap();
+        assert_eq!(result, ActionResult::OutOfArrows);
+        assert_eq!(env.score(), score);
+    }
+
+    #[test]
+    fn shoots_into_the_wall() {
+        let mut env = world("A _ _ _\nG _ _ W\n");
+        let result = env.step(&Action::Shoot(Direction::East)).unwrap();
+        assert_eq!(result, ActionResult::Ok);
+        assert_eq!(env.arrows(), 0);
+        assert_eq!(
+            *env.grid().room_at(&Pos::new(1, 3)).get_kind(),
+            RoomKind::Wumpus
+        );
+        assert!(!env.observation().senses().contains(&Sense::Scream));
+    }
+
+    #[test]
+    fn turns_around_under_aima() {
+        let mut env = world("A _ _ W\n_ _ _ G\n").with_ruleset(Ruleset::Aima);
+        assert_eq!(env.observation().orientation(), Some(&Direction::East));
+        for direction in [
+            Direction::North,
+            Direction::West,
+            Direction::South,
+            Direction::East,
+        ] {
+            env.step(&Action::TurnLeft).unwrap();
+            assert_eq!(env.observation().orientation(), Some(&direction));
+        }
+        for direction in [
+            Direction::South,
+            Direction::West,
+            Direction::North,
+            Direction::East,
+        ] {
+            env.step(&Action::TurnRight).unwrap();
+            assert_eq!(env.observation().orientation(), Some(&direction));
+        }
+        assert_eq!(env.agent_position(), &Pos::new(0, 0));
+    }
+
+    #[test]
+    fn bumps_into_walls_under_aima() {
+        let mut env = world("A _ _ W\n_ _ _ G\n").with_ruleset(Ruleset::Aima);
+        env.step(&Action::TurnLeft).unwrap();
+        let result = env.step(&Action::Forward).unwrap();
+        assert_eq!(result, ActionResult::Sense(Sense::Bump(Direction::North)));
+        assert_eq!(env.agent_position(), &Pos::new(0, 0));
+        assert!(env.observation().percept().bump);
+
+        // The bump is only felt right after running into the wall
+        env.step(&Action::TurnRight).unwrap();
+        assert!(!env.observation().percept().bump);
+
+        env.step(&Action::Forward).unwrap();
+        assert_eq!(env.agent_position(), &Pos::new(0, 1));
+    }
+
+    #[test]
+    fn refuses_actions_of_the_other_ruleset() {
+        let mut env = world("A _ _ W\n_ _ _ G\n").with_ruleset(Ruleset::Aima);
+        for action in [
+            Action::Move(Direction::East),
+            Action::Shoot(Direction::East),
+        ] {
+            assert!(matches!(
+                env.step(&action),
+                Err(Error::ActionNotAllowed {
+                    ruleset: Ruleset::Aima,
+                    ..
+                })
+            ));
+        }
+
+        let mut env = world("A _ _ W\n_ _ _ G\n");
+        for action in [
+            Action::Forward,
+            Action::TurnLeft,
+            Action::TurnRight,
+            Action::ShootForward,
+        ] {
+            assert!(matches!(
+                env.step(&action),
+                Err(Error::ActionNotAllowed {
+                    ruleset: Ruleset::Absolute,
+                    ..
+                })
+            ));
+        }
+        assert_eq!(env.steps(), 0);
+        assert_eq!(env.arrows(), 1);
+    }
+
+    #[test]
+    fn escapes_with_the_gold() {
+        let mut env = world("A G _ W\n_ _ _ _\n");
+        env.step(&Action::Move(Direction::East)).unwrap();
+        let result = env.step(&Action::Grab).unwrap();
+        assert_eq!(result, ActionResult::Grabbed(Item::Gold));
+        assert_eq!((env.gold(), env.score()), (1, -1));
+        assert!(!env.observation().senses().contains(&Sense::Glitter));
+
+        // There's no climbing out away from the entrance
+        let result = env.step(&Action::Climb).unwrap();
+        assert_eq!(result, ActionResult::Sense(Sense::Ceil));
+        assert_eq!(env.outcome(), None);
+
+        env.step(&Action::Move(Direction::West)).unwrap();
+        let result = env.step(&Action::Climb).unwrap();
+        assert_eq!(result, ActionResult::GameOver);
+        assert_eq!(env.outcome(), Some(&Outcome::Escaped { gold: 1 }));
+        assert_eq!(env.score(), 998);
+
+        assert!(matches!(env.step(&Action::Climb), Err(Error::EpisodeOver)));
+        assert_eq!(env.score(), 998);
+    }
+
+    #[test]
+    fn climbs_out_empty_handed() {
+        let mut env = world("A G _ W\n_ _ _ _\n");
+        let result = env.step(&Action::Climb).unwrap();
+        assert_eq!(result, ActionResult::GameOver);
+        assert_eq!(env.outcome(), Some(&Outcome::EmptyHanded));
+        assert_eq!(env.score(), 0);
+    }
+
+    #[test]
+    fn rewards_the_gold_on_grabbing() {
+        let mut env = world("A G _ W\n_ _ _ _\n").with_scoring(ScoringRules {
+            gold_reward_on: GoldReward::OnGrab,
+            ..ScoringRules::classic()
+        });
+        env.step(&Action::Move(Direction::East)).unwrap();
+        env.step(&Action::Grab).unwrap();
+        assert_eq!(env.score(), 999);
+
+        env.step(&Action::Move(Direction::West)).unwrap();
+        env.step(&Action::Climb).unwrap();
+        assert_eq!(env.outcome(), Some(&Outcome::Escaped { gold: 1 }));
+        assert_eq!(env.score(), 998);
+    }
+
+    #[test]
+    fn resets_to_the_world_of_a_seed() {
+        let mut env = Environment::generate(&WorldConfig::default(), 1).unwrap();
+        env.reset(Some(5)).unwrap();
+        let world = Map::from_env(&env).to_string();
+        assert_eq!(env.seed(), Some(5));
+
+        env.reset(Some(6)).unwrap();
+        assert_ne!(Map::from_env(&env).to_string(), world);
+
+        let obs = env.reset(Some(5)).unwrap();
+        assert_eq!(Map::from_env(&env).to_string(), world);
+        assert_eq!(obs.position(), env.start_position());
+        assert_eq!((env.steps(), env.score()), (0, 0));
+    }
+
+    #[test]
+    fn resets_other_worlds_without_a_seed_only() {
+        let mut env = world("A G _ W\n_ _ _ _\n");
+        env.step(&Action::Move(Direction::East)).unwrap();
+        assert!(matches!(env.reset(Some(1)), Err(Error::NotGenerated)));
+
+        let obs = env.reset(None).unwrap();
+        assert_eq!(obs.position(), &Pos::new(0, 0));
+        assert_eq!(env.agent_position(), &Pos::new(0, 0));
+    }
+
+    #[test]
+    fn rewards_the_change_in_score() {
+        let mut env = world("A G _ W\n_ _ _ _\n");
+        let mut total = 0;
+        for (action, reward) in [
+            (Action::Move(Direction::East), -1),
+            (Action::Grab, 0),
+            (Action::Shoot(Direction::East), -10),
+            (Action::Move(Direction::West), -1),
+        ] {
+            let step = env.step_with_reward(&action).unwrap();
+            assert_eq!(step.reward, reward);
+            assert!(!step.terminated && !step.truncated);
+            assert_eq!(step.observation.position(), env.agent_position());
+            total += step.reward;
+        }
+
+        let step = env.step_with_reward(&Action::Climb).unwrap();
+        assert_eq!(step.reward, 1000);
+        assert!(step.terminated && !step.truncated);
+        assert_eq!(step.info, ActionResult::GameOver);
+        assert_eq!(total + step.reward, env.score());
+    }
+
+    #[test]
+    fn restores_a_snapshot() {
+        let mut env = world("A _ _ W\n_ _ _ G\n");
+        env.step(&Action::Move(Direction::East)).unwrap();
+        let snapshot = env.snapshot();
+
+        env.step(&Action::Shoot(Direction::East)).unwrap();
+        for direction in [Direction::South, Direction::East, Direction::East] {
+            env.step(&Action::Move(direction)).unwrap();
+        }
+        env.step(&Action::Grab).unwrap();
+        assert_eq!((env.arrows(), env.gold()), (0, 1));
+
+        let check = |env: &Environment| {
+            assert_eq!(env.agent_position(), &Pos::new(0, 1));
+            assert_eq!(env.observation().position(), &Pos::new(0, 1));
+            assert_eq!((env.score(), env.steps()), (-1, 1));
+            assert_eq!((env.arrows(), env.gold()), (1, 0));
+            assert_eq!(env.outcome(), None);
+            let room = |row, col| env.grid().room_at(&Pos::new(row, col));
+            assert_eq!(*room(0, 3).get_kind(), RoomKind::Wumpus);
+            assert_eq!(*room(1, 3).get_kind(), RoomKind::Gold);
+            assert!(room(0, 0).is_visited() && room(0, 1).is_visited());
+            assert!(!room(1, 1).is_visited() && !room(1, 3).is_visited());
+        };
+        env.restore(&snapshot);
+        check(&env);
+
+        // Into another environment, which then plays the episode on
+        let mut other = Environment::new(GridType::Classic);
+        other.restore(&snapshot);
+        check(&other);
+        let result = other.step(&Action::Shoot(Direction::East)).unwrap();
+        assert_eq!(result, ActionResult::Sense(Sense::Scream));
+    }
+
+    /// An environment on a small world, scored with `scoring`.
+    fn limited(scoring: ScoringRules) -> Environment {
+        world("A _ _ W\n_ _ _ G\n").with_scoring(scoring)
+    }
+
+    #[test]
+    fn cuts_episodes_short_once_out_of_steps() {
+        let scoring = ScoringRules {
+            step_limit: Some(3),
+            step_limit_penalty: 50,
+            repeat_limit_penalty: 7,
+            ..ScoringRules::classic()
+        };
+        let mut env = limited(scoring.clone());
+        for direction in [Direction::East, Direction::West] {
+            assert_eq!(
+                env.step(&Action::Move(direction)).unwrap(),
+                ActionResult::Ok
+            );
+        }
+        assert_eq!(env.outcome(), None);
+
+        let result = env.step(&Action::Move(Direction::East)).unwrap();
+        assert_eq!(result, ActionResult::GameOver);
+        assert_eq!(
+            env.outcome(),
+            Some(&Outcome::Truncated(Truncation::StepLimit))
+        );
+        assert_eq!(env.score(), -53);
+        assert!(matches!(env.step(&Action::Climb), Err(Error::EpisodeOver)));
+        assert_eq!(env.score(), -53);
+
+        // The Gym step keeps the result of the last action
+        let mut env = limited(scoring);
+        env.step_with_reward(&Action::Move(Direction::East))
+            .unwrap();
+        env.step_with_reward(&Action::Move(Direction::West))
+            .unwrap();
+        let step = env
+            .step_with_reward(&Action::Move(Direction::East))
+            .unwrap();
+        assert_eq!(step.info, ActionResult::Ok);
+        assert!(step.truncated && !step.terminated);
+        assert_eq!(step.reward, -51);
+    }
+
+    #[test]
+    fn cuts_episodes_short_going_around_in_circles() {
+        let mut env = limited(ScoringRules {
+            repeat_limit: Some(1),
+            step_limit_penalty: 50,
+            repeat_limit_penalty: 7,
+            ..ScoringRules::classic()
+        });
+        // Back in a room once more rooms were visited is a new
+        // state, and so is back at the start after the first move
+        for direction in [
+            Direction::East,
+            Direction::South,
+            Direction::North,
+            Direction::West,
+        ] {
+            assert_eq!(
+                env.step(&Action::Move(direction)).unwrap(),
+                ActionResult::Ok
+            );
+        }
+        assert_eq!(env.outcome(), None);
+
+        let step = env
+            .step_with_reward(&Action::Move(Direction::East))
+            .unwrap();
+        assert_eq!(step.info, ActionResult::Ok);
+        assert!(step.truncated && !step.terminated);
+        assert_eq!(
+            env.outcome(),
+            Some(&Outcome::Truncated(Truncation::RepeatedState))
+        );
+        assert_eq!(env.score(), -12);
+    }
+}
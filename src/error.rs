@@ -0,0 +1,127 @@
+use std::fmt::Display;
+
+use crate::{
+    agent::{Action, Direction},
+    env::{Ruleset, Sense},
+    external::ExternalError,
+    grid::Pos,
+    map::MapError,
+    replay::ReplayError,
+    world::ConfigError,
+};
+
+/// Errors raised by the game engine, which embedding
+/// applications can recover from.
+#[derive(Debug)]
+pub enum Error {
+    /// An action that isn't part of the active ruleset
+    ActionNotAllowed {
+        /// The action taken
+        action: Action,
+        /// The active ruleset
+        ruleset: Ruleset,
+    },
+    /// An action taken once the episode is over
+    EpisodeOver,
+    /// A seed given to reset a world that wasn't generated from
+    /// a config, such as the classic world or a map
+    NotGenerated,
+    /// Moving from `pos` towards `direction` leaves the grid
+    OutOfBounds {
+        /// Where the move starts
+        pos: Pos,
+        /// Where it heads
+        direction: Direction,
+    },
+    /// A position that lies outside the grid
+    OffGrid(Pos),
+    /// A sense that doesn't belong where it was given
+    UnexpectedSense(Sense),
+    /// A Monte Carlo agent asked to run no simulation per step
+    NoIterations,
+    /// An agent that can't play by the ruleset of its environment
+    UnsupportedRuleset {
+        /// Name of the agent
+        agent: &'static str,
+        /// The ruleset it can't play by
+        ruleset: Ruleset,
+    },
+    /// An event log that couldn't be written
+    Io(std::io::Error),
+    /// A map file that couldn't be loaded or saved
+    Map(MapError),
+    /// A world config that couldn't generate a world
+    Config(ConfigError),
+    /// A replay that couldn't be loaded or re-executed
+    Replay(ReplayError),
+    /// An external agent that couldn't be talked to
+    External(ExternalError),
+}
+
+impl Display for Error {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Error::ActionNotAllowed { action, ruleset } => f.write_fmt(format_args!(
+                "{:?} is not an action of the {:?} ruleset",
+                action, ruleset
+            )),
+            Error::EpisodeOver => f.write_str("the episode is over"),
+            Error::NotGenerated => f.write_str("only generated worlds can be reset with a seed"),
+            Error::OutOfBounds { pos, direction } => f.write_fmt(format_args!(
+                "moving {:?} from {} leaves the grid",
+                direction, pos
+            )),
+            Error::OffGrid(pos) => f.write_fmt(format_args!("{} lies outside the grid", pos)),
+            Error::UnexpectedSense(sense) => {
+                f.write_fmt(format_args!("{:?} is not expected here", sense))
+            }
+            Error::NoIterations => f.write_str("the agent needs at least one iteration per step"),
+            Error::UnsupportedRuleset { agent, ruleset } => f.write_fmt(format_args!(
+                "the {} agent can't play by the {:?} ruleset",
+                agent, ruleset
+            )),
+            Error::Io(err) => err.fmt(f),
+            Error::Map(err) => err.fmt(f),
+            Error::Config(err) => err.fmt(f),
+            Error::Replay(err) => err.fmt(f),
+            Error::External(err) => err.fmt(f),
+        }
+    }
+}
+
+impl std::error::Error for Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Error::Io(err) => Some(err),
+            Error::Map(err) => Some(err),
+            Error::Config(err) => Some(err),
+            Error::Replay(err) => Some(err),
+            Error::External(err) => Some(err),
+            _ => None,
+        }
+    }
+}
+
+impl From<MapError> for Error {
+    fn from(err: MapError) -> Self {
+        Error::Map(err)
+    }
+}
+
+impl From<ConfigError> for Error {
+    fn from(err: ConfigError) -> Self {
+        Error::Config(err)
+    }
+}
+
+impl From<ReplayError> for Error {
+    fn from(err: ReplayError) -> Self {
+        Error::Replay(err)
+    }
+}
+
+impl From<ExternalError> for Error {
+    fn from(err: ExternalError) -> Self {
+        Error::External(err)
+    }
+}
@@ -0,0 +1,245 @@
+use std::{
+    fs::File,
+    io::{BufWriter, Write},
+    path::Path,
+};
+
+use crate::{
+    agent::Action,
+    env::{ActionResult, Environment, Sense},
+    error::Error,
+    kb::KbStats,
+    replay::{format_action, format_result},
+};
+
+/// A machine-readable trace of the steps taken in a run, written
+/// as JSON lines: one object per step, holding the episode and
+/// step indices, the agent's position and percept after the
+/// step, the action, its result, the score, and the knowledge
+/// base queries made to choose the action (`null` for agents
+/// without a knowledge base). Actions and results are written
+/// the way replays write them. The percept holds a bump after
+/// running into a wall under either ruleset.
+///
+/// ```text
+/// {"episode":1,"step":1,"position":{"row":3,"col":1},"percept":{"stench":false,"breeze":true,"glitter":false,"bump":false,"scream":false},"action":"mv e","result":"ok","score":-1,"kb_queries":41,"kb_cache_hits":12}
+/// ```
+///
+/// Undoing steps is logged as well, with the step the episode
+/// went back to and its score there. The steps of the episode
+/// logged after that one were taken back.
+///
+/// ```text
+/// {"episode":1,"undo_to":0,"score":0}
+/// ```
+pub struct EventLog {
+    out: Box<dyn Write + Send>,
+    episode: usize,
+}
+
+impl EventLog {
+    /// Writes the log to `out`.
+    pub fn new(out: impl Write + Send + 'static) -> Self {
+        Self {
+            out: Box::new(out),
+            episode: 0,
+        }
+    }
+
+    /// Writes the log to a file created at `path`.
+    pub fn create(path: impl AsRef<Path>) -> Result<Self, Error> {
+        let file = File::create(path).map_err(Error::Io)?;
+        Ok(Self::new(BufWriter::new(file)))
+    }
+
+    /// Marks the start of a new episode, whose steps are logged
+    /// with the next episode index.
+    pub fn start_episode(&mut self) {
+        self.episode += 1;
+    }
+
+    /// Logs the next steps with the episode index `episode`, for
+    /// runs whose episodes interleave.
+    pub fn set_episode(&mut self, episode: usize) {
+        self.episode = episode;
+    }
+
+    /// Logs the step of `env` that just took `action`, leading to
+    /// `result`. `kb` holds the knowledge base queries made to
+    /// choose the action.
+    pub fn record(
+        &mut self,
+        env: &Environment,
+        action: &Action,
+        result: &ActionResult,
+        kb: Option<KbStats>,
+    ) -> Result<(), Error> {
+        let pos = env.agent_position();
+        // Only the AIMA ruleset keeps the bump in the observation
+        let mut percept = env.observation().percept();
+        percept.bump |= matches!(result, ActionResult::Sense(Sense::Bump(_)));
+        let (queries, cache_hits) = match kb {
+            Some(stats) => (stats.queries.to_string(), stats.cache_hits.to_string()),
+            None => ("null".to_string(), "null".to_string()),
+        };
+
+        writeln!(
+            self.out,
+            "{{\"episode\":{},\"step\":{},\"position\":{{\"row\":{},\"col\":{}}},\
+             \"percept\":{{\"stench\":{},\"breeze\":{},\"glitter\":{},\"bump\":{},\"scream\":{}}},\
+             \"action\":{},\"result\":{},\"score\":{},\"kb_queries\":{},\"kb_cache_hits\":{}}}",
+            self.episode.max(1),
+            env.steps(),
+            pos.row,
+            pos.col,
+            percept.stench,
+            percept.breeze,
+            percept.glitter,
+            percept.bump,
+            percept.scream,
+            json_string(&format_action(action)),
+            json_string(&format_result(result)),
+            env.score(),
+            queries,
+            cache_hits,
+        )
+        .and_then(|()| self.out.flush())
+        .map_err(Error::Io)
+    }
+
+    /// Logs that `env` went back to an earlier step, undoing the
+    /// ones logged after it.
+    pub fn record_undo(&mut self, env: &Environment) -> Result<(), Error> {
+        writeln!(
+            self.out,
+            "{{\"episode\":{},\"undo_to\":{},\"score\":{}}}",
+            self.episode.max(1),
+            env.steps(),
+            env.score(),
+        )
+        .and_then(|()| self.out.flush())
+        .map_err(Error::Io)
+    }
+}
+
+/// Quotes `text` as a JSON string.
+pub(crate) fn json_string(text: &str) -> String {
+    let mut quoted = String::with_capacity(text.len() + 2);
+    quoted.push('"');
+    for c in text.chars() {
+        match c {
+            '"' => quoted.push_str("\\\""),
+            '\\' => quoted.push_str("\\\\"),
+            c if c.is_control() => quoted.push_str(&format!("\\u{:04x}", c as u32)),
+            c => quoted.push(c),
+        }
+    }
+    quoted.push('"');
+    quoted
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::{Arc, Mutex};
+
+    use super::*;
+    use crate::{agent::Direction, env::Ruleset, map::Map};
+
+    /// A writer whose output can be read once the log is done.
+    #[derive(Clone, Default)]
+    struct Buffer(Arc<Mutex<Vec<u8>>>);
+
+    impl Write for Buffer {
+        fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+            self.0.lock().unwrap().write(buf)
+        }
+
+        fn flush(&mut self) -> std::io::Result<()> {
+            Ok(())
+        }
+    }
+
+    impl Buffer {
+        fn lines(&self) -> Vec<String> {
+            let text = String::from_utf8(self.0.lock().unwrap().clone()).unwrap();
+            text.lines().map(str::to_string).collect()
+        }
+    }
+
+    /// Takes `action` in `env`, logging it to `log`.
+    fn step(log: &mut EventLog, env: &mut Environment, action: Action) {
+        let result = env.step(&action).unwrap();
+        log.record(env, &action, &result, None).unwrap();
+    }
+
+    #[test]
+    fn logs_steps_and_undos() {
+        let buffer = Buffer::default();
+        let mut log = EventLog::new(buffer.clone());
+        let mut env = Map::parse("A _ W\n_ P G\n").unwrap().into_environment();
+
+        log.start_episode();
+        step(&mut log, &mut env, Action::Move(Direction::East));
+        log.record(
+            &env,
+            &Action::Grab,
+            &ActionResult::Ok,
+            Some(KbStats {
+                queries: 41,
+                cache_hits: 12,
+            }),
+        )
+        .unwrap();
+        env.initialize();
+        log.record_undo(&env).unwrap();
+        log.start_episode();
+        step(&mut log, &mut env, Action::Shoot(Direction::East));
+
+        assert_eq!(
+            buffer.lines(),
+            [
+                "{\"episode\":1,\"step\":1,\"position\":{\"row\":0,\"col\":1},\
+                 \"percept\":{\"stench\":true,\"breeze\":true,\"glitter\":false,\"bump\":false,\"scream\":false},\
+                 \"action\":\"mv e\",\"result\":\"ok\",\"score\":-1,\"kb_queries\":null,\"kb_cache_hits\":null}",
+                "{\"episode\":1,\"step\":1,\"position\":{\"row\":0,\"col\":1},\
+                 \"percept\":{\"stench\":true,\"breeze\":true,\"glitter\":false,\"bump\":false,\"scream\":false},\
+                 \"action\":\"gr\",\"result\":\"ok\",\"score\":-1,\"kb_queries\":41,\"kb_cache_hits\":12}",
+                "{\"episode\":1,\"undo_to\":0,\"score\":0}",
+                "{\"episode\":2,\"step\":1,\"position\":{\"row\":0,\"col\":0},\
+                 \"percept\":{\"stench\":false,\"breeze\":false,\"glitter\":false,\"bump\":false,\"scream\":true},\
+                 \"action\":\"sh e\",\"result\":\"scream\",\"score\":-10,\"kb_queries\":null,\"kb_cache_hits\":null}",
+            ]
+        );
+    }
+
+    #[test]
+    fn logs_bumps_under_either_ruleset() {
+        for ruleset in [Ruleset::Absolute, Ruleset::Aima] {
+            let buffer = Buffer::default();
+            let mut log = EventLog::new(buffer.clone());
+            let mut env = Map::parse("A _ W\n_ P G\n")
+                .unwrap()
+                .into_environment()
+                .with_ruleset(ruleset);
+            let action = match ruleset {
+                Ruleset::Absolute => Action::Move(Direction::West),
+                Ruleset::Aima => {
+                    step(&mut log, &mut env, Action::TurnLeft);
+                    Action::Forward
+                }
+            };
+            step(&mut log, &mut env, action);
+
+            let lines = buffer.lines();
+            let last = lines.last().unwrap();
+            assert!(last.contains("\"bump\":true"), "{}", last);
+            assert!(last.contains("\"result\":\"bump "), "{}", last);
+            // Turning towards the wall bumps into nothing
+            assert!(
+                lines[..lines.len() - 1]
+                    .iter()
+                    .all(|line| line.contains("\"bump\":false"))
+            );
+        }
+    }
+}
@@ -0,0 +1,690 @@
+use std::{
+    collections::HashMap,
+    fmt::Display,
+    io::{BufRead, BufReader, Write},
+    process::{Child, Command, Stdio},
+    sync::mpsc::{self, Receiver, RecvTimeoutError, Sender},
+    time::{Duration, Instant},
+};
+
+use crate::{
+    agent::{Action, Agent, Observation},
+    env::{ActionResult, Environment},
+    error::Error,
+    events::json_string,
+    replay::{format_action, format_direction, format_result, parse_action},
+};
+
+/// Time an external agent is given to reply, unless told otherwise.
+pub const DEFAULT_TIMEOUT: Duration = Duration::from_secs(1);
+/// Longest time an external agent is given to exit once its input
+/// is closed, so that dropping it doesn't hold up a run for long.
+const SHUTDOWN_GRACE: Duration = Duration::from_millis(250);
+
+/// An agent played by another program, in any language, talking
+/// over its standard input and output with one JSON object per
+/// line.
+///
+/// The program is sent these messages, telling them apart by
+/// their `type`:
+///
+/// ```text
+/// {"type":"episode_start","rows":4,"cols":4,"observation":{...}}
+/// {"type":"act","observation":{...}}
+/// {"type":"result","action":"mv n","result":"ok","reward":-1}
+/// {"type":"episode_end","score":974}
+/// ```
+///
+/// and replies to each `act` message, and to it only, with the
+/// action to take, written the way actions are typed in game:
+///
+/// ```text
+/// {"action":"mv n"}
+/// ```
+///
+/// Observations hold the agent's position, its percept, the
+/// directions it can move towards and, under the AIMA ruleset,
+/// the direction it faces:
+///
+/// ```text
+/// {"position":{"row":3,"col":0},"percept":{"stench":false,"breeze":true,"glitter":false,"bump":false,"scream":false},"directions":["n","e"],"orientation":null}
+/// ```
+///
+/// Anything the program writes to its standard error is passed
+/// through, to debug it. The program is killed when it fails to
+/// reply or to read its input in time. When the agent is dropped,
+/// its input is closed and it is given a short grace period to
+/// exit before it is killed. The same program plays every episode a benchmark
+/// worker hands it, rather than being started over for each.
+pub struct ExternalAgent {
+    child: Child,
+    /// Lines for the program's input, written on a separate
+    /// thread so that writes can be waited for with a timeout
+    lines: Option<Sender<String>>,
+    /// Outcome of each line written
+    written: Receiver<std::io::Result<()>>,
+    /// Lines of the program's output, read on a separate thread
+    /// so that replies can be waited for with a timeout
+    replies: Receiver<std::io::Result<String>>,
+    timeout: Duration,
+    rows: usize,
+    cols: usize,
+    /// Error raised by a notification, reported on the next `act`
+    pending: Option<ExternalError>,
+}
+
+impl ExternalAgent {
+    /// Starts `program` with `args` to play on a grid of `rows` by
+    /// `cols`, waiting up to `timeout` for each of its replies and
+    /// for it to read each message.
+    pub fn spawn(
+        program: &str,
+        args: &[String],
+        timeout: Duration,
+        rows: usize,
+        cols: usize,
+    ) -> Result<Self, Error> {
+        let mut child = Command::new(program)
+            .args(args)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::inherit())
+            .spawn()
+            .map_err(|err| ExternalError::Spawn {
+                command: program.to_string(),
+                source: err,
+            })?;
+
+        let mut stdin = child.stdin.take().expect("stdin is piped");
+        let (lines, to_write) = mpsc::channel::<String>();
+        let (sender, written) = mpsc::channel();
+        std::thread::spawn(move || {
+            // Ends once the agent stops sending, closing the input
+            for line in to_write {
+                let result = writeln!(stdin, "{}", line).and_then(|()| stdin.flush());
+                let failed = result.is_err();
+                if sender.send(result).is_err() || failed {
+                    break;
+                }
+            }
+        });
+
+        let stdout = child.stdout.take().expect("stdout is piped");
+        let (sender, replies) = mpsc::channel();
+        std::thread::spawn(move || {
+            for line in BufReader::new(stdout).lines() {
+                if sender.send(line).is_err() {
+                    break;
+                }
+            }
+        });
+
+        Ok(Self {
+            child,
+            lines: Some(lines),
+            written,
+            replies,
+            timeout,
+            rows,
+            cols,
+            pending: None,
+        })
+    }
+
+    /// Writes `message` to the program on its own line, killing it
+    /// when it doesn't read it in time.
+    fn send(&mut self, message: &str) -> Result<(), ExternalError> {
+        let lines = self.lines.as_ref().ok_or(ExternalError::Exited)?;
+        lines
+            .send(message.to_string())
+            .map_err(|_| ExternalError::Exited)?;
+        match self.written.recv_timeout(self.timeout) {
+            Ok(Ok(())) => Ok(()),
+            Ok(Err(err)) if err.kind() == std::io::ErrorKind::BrokenPipe => {
+                Err(ExternalError::Exited)
+            }
+            Ok(Err(err)) => Err(ExternalError::Io(err)),
+            Err(RecvTimeoutError::Disconnected) => Err(ExternalError::Exited),
+            Err(RecvTimeoutError::Timeout) => {
+                self.stop();
+                Err(ExternalError::Timeout(self.timeout))
+            }
+        }
+    }
+
+    /// Sends a notification, keeping any error for the next `act`.
+    fn notify(&mut self, message: &str) {
+        if self.pending.is_none()
+            && let Err(err) = self.send(message)
+        {
+            self.pending = Some(err);
+        }
+    }
+
+    /// Waits for the next line of the program, killing it when it
+    /// doesn't come in time.
+    fn receive(&mut self) -> Result<String, ExternalError> {
+        match self.replies.recv_timeout(self.timeout) {
+            Ok(Ok(line)) => Ok(line),
+            Ok(Err(err)) => Err(ExternalError::Io(err)),
+            Err(RecvTimeoutError::Disconnected) => Err(ExternalError::Exited),
+            Err(RecvTimeoutError::Timeout) => {
+                self.stop();
+                Err(ExternalError::Timeout(self.timeout))
+            }
+        }
+    }
+
+    /// Kills the program, after which every call fails.
+    fn stop(&mut self) {
+        self.lines = None;
+        // The program may have exited already
+        let _ = self.child.kill();
+        let _ = self.child.wait();
+    }
+
+    /// Closes the program's input and gives it until the timeout,
+    /// or `SHUTDOWN_GRACE` if shorter, to exit on its own, killing
+    /// it past that. Blocks for as long, checking every 5 ms.
+    fn shut_down(&mut self) {
+        self.lines = None;
+        let deadline = Instant::now() + self.timeout.min(SHUTDOWN_GRACE);
+        while Instant::now() < deadline {
+            match self.child.try_wait() {
+                Ok(None) => std::thread::sleep(Duration::from_millis(5)),
+                // Exited, or can't be waited for anymore
+                _ => return,
+            }
+        }
+        self.stop();
+    }
+}
+
+impl Agent for ExternalAgent {
+    fn act(&mut self, obs: &Observation) -> Result<Action, Error> {
+        if let Some(err) = self.pending.take() {
+            return Err(err.into());
+        }
+        self.send(&format!(
+            "{{\"type\":\"act\",\"observation\":{}}}",
+            observation_json(obs)
+        ))?;
+        let reply = self.receive()?;
+        Ok(parse_reply(&reply)?)
+    }
+
+    fn on_episode_start(&mut self, obs: &Observation) {
+        let message = format!(
+            "{{\"type\":\"episode_start\",\"rows\":{},\"cols\":{},\"observation\":{}}}",
+            self.rows,
+            self.cols,
+            observation_json(obs)
+        );
+        self.notify(&message);
+    }
+
+    fn on_result(&mut self, action: &Action, result: &ActionResult, reward: isize) {
+        let message = format!(
+            "{{\"type\":\"result\",\"action\":{},\"result\":{},\"reward\":{}}}",
+            json_string(&format_action(action)),
+            json_string(&format_result(result)),
+            reward
+        );
+        self.notify(&message);
+    }
+
+    fn on_episode_end(&mut self, score: isize) {
+        self.notify(&format!("{{\"type\":\"episode_end\",\"score\":{}}}", score));
+    }
+
+    fn reuse_for(&mut self, env: &Environment) -> bool {
+        if self.lines.is_none() || self.pending.is_some() {
+            return false;
+        }
+        self.rows = env.grid().nrows();
+        self.cols = env.grid().ncols();
+        true
+    }
+}
+
+impl Drop for ExternalAgent {
+    fn drop(&mut self) {
+        self.shut_down();
+    }
+}
+
+/// Errors raised while talking to an external agent.
+#[derive(Debug)]
+pub enum ExternalError {
+    /// A command without any program to run
+    EmptyCommand,
+    /// A command with a quote left open
+    UnclosedQuote(String),
+    /// A program that couldn't be started
+    Spawn {
+        /// The command the program was started with
+        command: String,
+        /// Why it couldn't be started
+        source: std::io::Error,
+    },
+    /// Reading from or writing to the program failed
+    Io(std::io::Error),
+    /// The program didn't reply, or read what it was sent, within
+    /// this time, and was killed
+    Timeout(Duration),
+    /// The program exited, or closed its input or output
+    Exited,
+    /// A reply that isn't a valid action message
+    Malformed {
+        /// The reply, as received
+        reply: String,
+        /// What is wrong with it
+        reason: String,
+    },
+}
+
+impl Display for ExternalError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ExternalError::EmptyCommand => f.write_str("no command given for the external agent"),
+            ExternalError::UnclosedQuote(command) => {
+                f.write_fmt(format_args!("unclosed quote in command `{}`", command))
+            }
+            ExternalError::Spawn { command, source } => f.write_fmt(format_args!(
+                "couldn't start external agent `{}`: {}",
+                command, source
+            )),
+            ExternalError::Io(err) => err.fmt(f),
+            ExternalError::Timeout(timeout) => f.write_fmt(format_args!(
+                "external agent didn't reply or read its input within {:?}",
+                timeout
+            )),
+            ExternalError::Exited => f.write_str("external agent exited"),
+            ExternalError::Malformed { reply, reason } => f.write_fmt(format_args!(
+                "malformed reply from external agent ({}): {}",
+                reason, reply
+            )),
+        }
+    }
+}
+
+impl std::error::Error for ExternalError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            ExternalError::Spawn { source, .. } => Some(source),
+            ExternalError::Io(err) => Some(err),
+            _ => None,
+        }
+    }
+}
+
+/// Splits `command` into a program and its arguments, on
+/// whitespace outside of single or double quotes, the way a shell
+/// would. A backslash takes the next character as is, except
+/// within single quotes.
+pub fn split_command(command: &str) -> Result<(String, Vec<String>), ExternalError> {
+    let mut words = Vec::new();
+    let mut word: Option<String> = None;
+    let mut quote = None;
+    let mut chars = command.chars();
+    while let Some(c) = chars.next() {
+        match (quote, c) {
+            (Some(q), c) if c == q => quote = None,
+            (None, '\'' | '"') => {
+                quote = Some(c);
+                word.get_or_insert_default();
+            }
+            (Some('\''), c) => word.get_or_insert_default().push(c),
+            (_, '\\') => {
+                let escaped = chars.next().unwrap_or('\\');
+                word.get_or_insert_default().push(escaped);
+            }
+            (None, c) if c.is_whitespace() => words.extend(word.take()),
+            (_, c) => word.get_or_insert_default().push(c),
+        }
+    }
+    if quote.is_some() {
+        return Err(ExternalError::UnclosedQuote(command.to_string()));
+    }
+    words.extend(word);
+    let mut words = words.into_iter();
+    let program = words.next().ok_or(ExternalError::EmptyCommand)?;
+    Ok((program, words.collect()))
+}
+
+fn observation_json(obs: &Observation) -> String {
+    let pos = obs.position();
+    let percept = obs.percept();
+    let mut directions: Vec<&str> = obs.directions().iter().map(format_direction).collect();
+    directions.sort();
+    let orientation = match obs.orientation() {
+        Some(direction) => json_string(format_direction(direction)),
+        None => "null".to_string(),
+    };
+
+    format!(
+        "{{\"position\":{{\"row\":{},\"col\":{}}},\
+         \"percept\":{{\"stench\":{},\"breeze\":{},\"glitter\":{},\"bump\":{},\"scream\":{}}},\
+         \"directions\":[{}],\"orientation\":{}}}",
+        pos.row,
+        pos.col,
+        percept.stench,
+        percept.breeze,
+        percept.glitter,
+        percept.bump,
+        percept.scream,
+        directions
+            .iter()
+            .map(|d| json_string(d))
+            .collect::<Vec<_>>()
+            .join(","),
+        orientation
+    )
+}
+
+/// Reads the action of a `{"action":"..."}` reply.
+fn parse_reply(reply: &str) -> Result<Action, ExternalError> {
+    let malformed = |reason: &str| ExternalError::Malformed {
+        reply: reply.to_string(),
+        reason: reason.to_string(),
+    };
+    let fields = parse_object(reply).map_err(malformed)?;
+    let action = fields
+        .get("action")
+        .ok_or_else(|| malformed("no `action` field"))?;
+    parse_action(action).ok_or_else(|| malformed(&format!("unknown action `{}`", action)))
+}
+
+/// Parses a JSON object whose values are all strings, numbers,
+/// booleans or `null`, keeping them as text.
+fn parse_object(text: &str) -> Result<HashMap<String, String>, &'static str> {
+    let mut chars = text.trim().chars().peekable();
+    let mut fields = HashMap::new();
+
+    let skip_whitespace = |chars: &mut std::iter::Peekable<std::str::Chars>| {
+        while chars.next_if(|c| c.is_whitespace()).is_some() {}
+    };
+
+    if chars.next() != Some('{') {
+        return Err("not a JSON object");
+    }
+    skip_whitespace(&mut chars);
+    if chars.next_if_eq(&'}').is_none() {
+        loop {
+            skip_whitespace(&mut chars);
+            let key = parse_string(&mut chars)?;
+            skip_whitespace(&mut chars);
+            if chars.next() != Some(':') {
+                return Err("expected `:` after a key");
+            }
+            skip_whitespace(&mut chars);
+            let value = match chars.peek() {
+                Some('"') => parse_string(&mut chars)?,
+                Some('{' | '[') => return Err("nested values aren't supported"),
+                _ => {
+                    let mut value = String::new();
+                    while let Some(c) = chars.next_if(|c| !matches!(c, ',' | '}')) {
+                        value.push(c);
+                    }
+                    value.trim_end().to_string()
+                }
+            };
+            fields.insert(key, value);
+            skip_whitespace(&mut chars);
+            match chars.next() {
+                Some(',') => {}
+                Some('}') => break,
+                _ => return Err("expected `,` or `}` after a value"),
+            }
+        }
+    }
+    skip_whitespace(&mut chars);
+    match chars.next() {
+        Some(_) => Err("unexpected text after the object"),
+        None => Ok(fields),
+    }
+}
+
+/// Parses a quoted JSON string, unescaping it.
+fn parse_string(chars: &mut std::iter::Peekable<std::str::Chars>) -> Result<String, &'static str> {
+    if chars.next() != Some('"') {
+        return Err("expected a string");
+    }
+    let mut string = String::new();
+    loop {
+        match chars.next().ok_or("unterminated string")? {
+            '"' => return Ok(string),
+            '\\' => string.push(match chars.next().ok_or("unterminated string")? {
+                '"' => '"',
+                '\\' => '\\',
+                '/' => '/',
+                'b' => '\u{8}',
+                'f' => '\u{c}',
+                'n' => '\n',
+                'r' => '\r',
+                't' => '\t',
+                'u' => {
+                    let code: String = chars.by_ref().take(4).collect();
+                    u32::from_str_radix(&code, 16)
+                        .ok()
+                        .and_then(char::from_u32)
+                        .ok_or("invalid unicode escape")?
+                }
+                _ => return Err("invalid escape"),
+            }),
+            c => string.push(c),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn fields(pairs: &[(&str, &str)]) -> HashMap<String, String> {
+        pairs
+            .iter()
+            .map(|(key, value)| (key.to_string(), value.to_string()))
+            .collect()
+    }
+
+    #[test]
+    fn parses_flat_objects() {
+        assert_eq!(parse_object("{}"), Ok(fields(&[])));
+        assert_eq!(parse_object(" { } "), Ok(fields(&[])));
+        assert_eq!(
+            parse_object(r#"{"action": "mv e", "steps": 12, "done": false, "note": null}"#),
+            Ok(fields(&[
+                ("action", "mv e"),
+                ("steps", "12"),
+                ("done", "false"),
+                ("note", "null"),
+            ]))
+        );
+    }
+
+    #[test]
+    fn unescapes_strings() {
+        assert_eq!(
+            parse_object(r#"{"say":"a \"quote\", a \\ and é\n"}"#),
+            Ok(fields(&[("say", "a \"quote\", a \\ and \u{e9}\n")]))
+        );
+    }
+
+    #[test]
+    fn rejects_malformed_objects() {
+        let cases = [
+            (r#"["action"]"#, "not a JSON object"),
+            (r#"{action: "cl"}"#, "expected a string"),
+            (r#"{"action" "cl"}"#, "expected `:` after a key"),
+            (
+                r#"{"action": {"kind": "cl"}}"#,
+                "nested values aren't supported",
+            ),
+            (r#"{"action": ["cl"]}"#, "nested values aren't supported"),
+            (
+                r#"{"action": "cl" "steps": 1}"#,
+                "expected `,` or `}` after a value",
+            ),
+            (
+                r#"{"action": "cl"} trailing"#,
+                "unexpected text after the object",
+            ),
+            (r#"{"action": "cl"#, "unterminated string"),
+            (r#"{"action": "\q"}"#, "invalid escape"),
+            (r#"{"action": "\uzzzz"}"#, "invalid unicode escape"),
+        ];
+        for (text, reason) in cases {
+            assert_eq!(parse_object(text), Err(reason), "{}", text);
+        }
+    }
+
+    #[test]
+    fn splits_commands() {
+        let split = |command: &str| {
+            split_command(command).map(|(program, args)| {
+                std::iter::once(program)
+                    .chain(args)
+                    .collect::<Vec<String>>()
+            })
+        };
+        assert_eq!(split("  java  MyAgent ").unwrap(), ["java", "MyAgent"]);
+        assert_eq!(
+            split(r#"python3 "/my bots/agent.py" 'it''s' -v"#).unwrap(),
+            ["python3", "/my bots/agent.py", "its", "-v"]
+        );
+        assert_eq!(
+            split(r#"./agent my\ world 'a\b' "say \"hi\"" ''"#).unwrap(),
+            ["./agent", "my world", r"a\b", r#"say "hi""#, ""]
+        );
+        assert!(matches!(split("   "), Err(ExternalError::EmptyCommand)));
+        assert!(matches!(
+            split("python3 'agent.py"),
+            Err(ExternalError::UnclosedQuote(command)) if command == "python3 'agent.py"
+        ));
+    }
+
+    #[test]
+    fn parses_replies() {
+        assert!(matches!(
+            parse_reply(r#"{"action":"mv n"}"#),
+            Ok(Action::Move(crate::agent::Direction::North))
+        ));
+        assert!(matches!(
+            parse_reply(r#"{"action":"fw"}"#),
+            Ok(Action::Forward)
+        ));
+        for (reply, expected) in [
+            ("cl", "not a JSON object"),
+            (r#"{"move":"cl"}"#, "no `action` field"),
+            (r#"{"action":"jump"}"#, "unknown action `jump`"),
+        ] {
+            assert!(
+                matches!(
+                    parse_reply(reply),
+                    Err(ExternalError::Malformed { reason, .. }) if reason == expected
+                ),
+                "{}",
+                reply
+            );
+        }
+    }
+
+    /// Starts `sh` running `script`, given `args` as `$1` and so
+    /// on, as an agent waiting `timeout` for each reply.
+    fn script(script: &str, args: &[&str], timeout: Duration) -> ExternalAgent {
+        let args: Vec<String> = ["-c", script, "sh"]
+            .iter()
+            .chain(args)
+            .map(|arg| arg.to_string())
+            .collect();
+        ExternalAgent::spawn("sh", &args, timeout, 4, 4).unwrap()
+    }
+
+    fn start_obs() -> Observation {
+        crate::env::Environment::new(crate::env::GridType::Classic)
+            .observation()
+            .clone()
+    }
+
+    #[test]
+    fn kills_programs_not_replying_in_time() {
+        let mut agent = script("cat > /dev/null", &[], Duration::from_millis(100));
+        let started = Instant::now();
+        let err = agent.act(&start_obs()).unwrap_err();
+        assert!(
+            matches!(err, Error::External(ExternalError::Timeout(_))),
+            "{}",
+            err
+        );
+        assert!(started.elapsed() < Duration::from_secs(2));
+        assert!(agent.child.try_wait().unwrap().is_some());
+        assert!(matches!(
+            agent.act(&start_obs()),
+            Err(Error::External(ExternalError::Exited))
+        ));
+    }
+
+    #[test]
+    fn reports_programs_exiting_early() {
+        let mut agent = script("exit 0", &[], Duration::from_secs(5));
+        let err = agent.act(&start_obs()).unwrap_err();
+        assert!(
+            matches!(err, Error::External(ExternalError::Exited)),
+            "{}",
+            err
+        );
+    }
+
+    #[test]
+    fn plays_episodes_on_and_exits_once_done() {
+        let log = std::env::temp_dir().join(format!("wumpus-agent-{}.log", std::process::id()));
+        let mut agent = script(
+            r#"echo start > "$1"
+            while read -r line; do
+                echo "$line" >> "$1"
+                case "$line" in *'"type":"act"'*) echo '{"action":"cl"}' ;; esac
+            done
+            echo done >> "$1""#,
+            &[log.to_str().unwrap()],
+            Duration::from_secs(5),
+        );
+        let mut env = crate::env::Environment::new(crate::env::GridType::Classic);
+        for _ in 0..2 {
+            env.reset(None).unwrap();
+            assert!(agent.reuse_for(&env));
+            crate::bench::play_episode(&mut env, &mut agent, |_, _, _| Ok(())).unwrap();
+        }
+        drop(agent);
+
+        let lines: Vec<String> = std::fs::read_to_string(&log)
+            .unwrap()
+            .lines()
+            .map(|line| {
+                let end = line.find(',').unwrap_or(line.len());
+                line[..end].to_string()
+            })
+            .collect();
+        std::fs::remove_file(&log).unwrap();
+        let episode = [
+            "{\"type\":\"episode_start\"",
+            "{\"type\":\"act\"",
+            "{\"type\":\"result\"",
+            "{\"type\":\"episode_end\"",
+        ];
+        let expected: Vec<&str> = std::iter::once("start")
+            .chain(episode)
+            .chain(episode)
+            .chain(["done"])
+            .collect();
+        assert_eq!(lines, expected);
+    }
+
+    #[test]
+    fn kills_programs_not_exiting_once_done() {
+        let agent = script("exec sleep 10", &[], Duration::from_secs(5));
+        let started = Instant::now();
+        drop(agent);
+        assert!(started.elapsed() < Duration::from_secs(2));
+    }
+}
@@ -1,46 +1,95 @@
-use std::ops::{Add, AddAssign, Index, IndexMut};
+use std::{
+    fmt::Display,
+    ops::{Index, IndexMut},
+};
 
 use crate::{
     agent::Direction,
     env::Sense,
+    error::Error,
     room::{Room, RoomKind},
 };
 
+/// A position on the grid, row 0 being the northmost one and
+/// column 0 the westmost one.
 #[derive(Clone, PartialEq, Debug, Eq, Hash)]
 pub struct Pos {
+    /// Row, counted from the north
     pub row: usize,
+    /// Column, counted from the west
     pub col: usize,
 }
 
 impl Pos {
+    /// The position at `row` and `col`.
     pub fn new(row: usize, col: usize) -> Pos {
         Pos { row, col }
     }
-}
 
-impl Add<&Direction> for &Pos {
-    type Output = Pos;
-
-    fn add(self, rhs: &Direction) -> Self::Output {
-        let mut result = self.clone();
-        match rhs {
-            Direction::North => result.row -= 1,
-            Direction::South => result.row += 1,
-            Direction::East => result.col += 1,
-            Direction::West => result.col -= 1,
+    /// Returns the position next to this one towards `direction`,
+    /// or an error when it would lie before row or column 0.
+    pub fn try_add(&self, direction: &Direction) -> Result<Pos, Error> {
+        let pos = match direction {
+            Direction::North => self.row.checked_sub(1).map(|row| Pos::new(row, self.col)),
+            Direction::South => self.row.checked_add(1).map(|row| Pos::new(row, self.col)),
+            Direction::East => self.col.checked_add(1).map(|col| Pos::new(self.row, col)),
+            Direction::West => self.col.checked_sub(1).map(|col| Pos::new(self.row, col)),
         };
-        result
+        pos.ok_or_else(|| Error::OutOfBounds {
+            pos: self.clone(),
+            direction: direction.clone(),
+        })
+    }
+}
+
+impl Display for Pos {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_fmt(format_args!("({}, {})", self.row, self.col))
     }
 }
 
-impl AddAssign<&Direction> for Pos {
-    fn add_assign(&mut self, rhs: &Direction) {
-        let p = &*self + rhs;
-        self.col = p.col;
-        self.row = p.row;
+/// A broken rule of the game found in a `Grid`.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Violation {
+    /// The cave has no Wumpus
+    NoWumpus,
+    /// The cave has more than one Wumpus, at these positions
+    MultipleWumpuses(Vec<Pos>),
+    /// The cave has no gold
+    NoGold,
+    /// The start position lies outside the grid
+    StartOutOfBounds(Pos),
+    /// The start position holds a pit or the Wumpus
+    UnsafeStart(Pos, RoomKind),
+}
+
+impl Display for Violation {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Violation::NoWumpus => f.write_str("there is no Wumpus in the cave"),
+            Violation::MultipleWumpuses(positions) => f.write_fmt(format_args!(
+                "there are {} Wumpuses in the cave, at {}",
+                positions.len(),
+                positions
+                    .iter()
+                    .map(|pos| pos.to_string())
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            )),
+            Violation::NoGold => f.write_str("there is no gold in the cave"),
+            Violation::StartOutOfBounds(pos) => {
+                f.write_fmt(format_args!("start position {} is outside the cave", pos))
+            }
+            Violation::UnsafeStart(pos, kind) => f.write_fmt(format_args!(
+                "start position {} is not safe, it holds '{}'",
+                pos, kind
+            )),
+        }
     }
 }
 
+/// The rooms of the world, laid out in rows and columns.
+#[derive(Clone, Debug)]
 pub struct Grid {
     cells: Vec<Vec<Room>>,
     nrows: usize,
@@ -48,6 +97,7 @@ pub struct Grid {
 }
 
 impl Grid {
+    /// A grid of `nrows` by `ncols` empty rooms.
     pub fn new(nrows: usize, ncols: usize) -> Grid {
         Grid {
             cells: (0..nrows)
@@ -58,21 +108,31 @@ impl Grid {
         }
     }
 
+    /// Adds `sense` to the room at `row` and `col`, going through
+    /// `Room::add_sense` so that only senses a room gives off end
+    /// up in it.
+    fn add_sense(&mut self, row: usize, col: usize, sense: &Sense) {
+        let added = self.cells[row][col].add_sense(sense.clone());
+        debug_assert!(added.is_ok(), "{:?} can't be given off by a room", sense);
+    }
+
     fn surround_cell(&mut self, row: usize, col: usize, with: &Sense) {
         if row as isize - 1 >= 0 {
-            self.cells[row - 1][col].add_sense(with.clone());
+            self.add_sense(row - 1, col, with);
         }
         if row + 1 <= self.nrows - 1 {
-            self.cells[row + 1][col].add_sense(with.clone());
+            self.add_sense(row + 1, col, with);
         }
         if col as isize - 1 >= 0 {
-            self.cells[row][col - 1].add_sense(with.clone());
+            self.add_sense(row, col - 1, with);
         }
         if col + 1 <= self.ncols - 1 {
-            self.cells[row][col + 1].add_sense(with.clone());
+            self.add_sense(row, col + 1, with);
         }
     }
 
+    /// Adds the senses the kind of each room gives off, to the
+    /// room itself or its neighbors.
     pub fn update_senses(&mut self) {
         for i in 0..self.nrows {
             for j in 0..self.ncols {
@@ -87,29 +147,100 @@ impl Grid {
                         self.surround_cell(i, j, &Sense::Stench);
                     }
                     RoomKind::Gold => {
-                        self.cells[i][j].add_sense(Sense::Glitter);
+                        self.add_sense(i, j, &Sense::Glitter);
                     }
                 }
             }
         }
     }
 
+    /// Number of rows.
     pub fn nrows(&self) -> usize {
         self.nrows
     }
 
+    /// Number of columns.
     pub fn ncols(&self) -> usize {
         self.ncols
     }
 
+    /// Returns the position next to `pos` towards `direction`,
+    /// or `None` when a wall is in the way.
+    pub fn neighbor(&self, pos: &Pos, direction: &Direction) -> Option<Pos> {
+        pos.try_add(direction)
+            .ok()
+            .filter(|next| self.contains(next))
+    }
+
+    /// Whether `pos` lies within the grid.
+    pub fn contains(&self, pos: &Pos) -> bool {
+        pos.row < self.nrows && pos.col < self.ncols
+    }
+
+    /// Returns the room at `pos`, or an error when it lies outside
+    /// the grid.
+    pub fn try_room_at(&self, pos: &Pos) -> Result<&Room, Error> {
+        match self.contains(pos) {
+            true => Ok(self.room_at(pos)),
+            false => Err(Error::OffGrid(pos.clone())),
+        }
+    }
+
+    /// Returns the room at `position`, which must lie within the
+    /// grid.
     pub fn room_at(&self, position: &Pos) -> &Room {
         &self.cells[position.row][position.col]
     }
 
+    /// Mutable access to the room at `position`, which must lie
+    /// within the grid.
     pub fn mut_room_at(&mut self, position: &Pos) -> &mut Room {
         &mut self.cells[position.row][position.col]
     }
 
+    /// Checks the grid against the rules of the game, with the
+    /// agent starting at `start`, and reports every violation.
+    /// Since a room holds a single kind, no room can contain both
+    /// the Wumpus and a pit, or gold in a pit. Senses are derived
+    /// from the rooms by `update_senses`, so they always agree.
+    pub fn validate(&self, start: &Pos) -> Result<(), Vec<Violation>> {
+        let mut violations = Vec::new();
+
+        let positions_of = |kind: RoomKind| -> Vec<Pos> {
+            (0..self.nrows)
+                .flat_map(|i| (0..self.ncols).map(move |j| Pos::new(i, j)))
+                .filter(|pos| *self.room_at(pos).get_kind() == kind)
+                .collect()
+        };
+
+        let wumpuses = positions_of(RoomKind::Wumpus);
+        match wumpuses.len() {
+            0 => violations.push(Violation::NoWumpus),
+            1 => {}
+            _ => violations.push(Violation::MultipleWumpuses(wumpuses)),
+        }
+
+        if positions_of(RoomKind::Gold).is_empty() {
+            violations.push(Violation::NoGold);
+        }
+
+        if !self.contains(start) {
+            violations.push(Violation::StartOutOfBounds(start.clone()));
+        } else {
+            let kind = self.room_at(start).get_kind();
+            if *kind == RoomKind::Pit || *kind == RoomKind::Wumpus {
+                violations.push(Violation::UnsafeStart(start.clone(), kind.clone()));
+            }
+        }
+
+        match violations.is_empty() {
+            true => Ok(()),
+            false => Err(violations),
+        }
+    }
+
+    /// Derives the senses of the rooms and marks them all as
+    /// unvisited, before an episode starts.
     pub fn initialize(&mut self) {
         self.update_senses();
         self.cells
@@ -6,6 +6,27 @@ use std::{
 
 use crate::logic::Statement;
 
+/// Counts of the queries a `KnowledgeBase` answered since it
+/// was created, including the ones it asked itself while
+/// proving others, but not the ones made by `ask_uncounted`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct KbStats {
+    /// Queries answered
+    pub queries: usize,
+    /// Queries answered from the cache
+    pub cache_hits: usize,
+}
+
+impl KbStats {
+    /// The queries made since `earlier` was taken.
+    pub fn since(&self, earlier: &KbStats) -> KbStats {
+        KbStats {
+            queries: self.queries.saturating_sub(earlier.queries),
+            cache_hits: self.cache_hits.saturating_sub(earlier.cache_hits),
+        }
+    }
+}
+
 /// A knowledge base (`KB`) is defined to be a set of logical
 /// statements, representing facts that an agent `“knows”`.
 ///
@@ -16,25 +37,47 @@ pub struct KnowledgeBase {
     facts: HashSet<Statement>,
     pending: HashSet<Statement>,
     cache: HashMap<Statement, bool>,
+    /// Statements each proven statement was entailed by
+    proofs: HashMap<Statement, Vec<Statement>>,
+    stats: KbStats,
 }
 
 impl KnowledgeBase {
+    /// An empty knowledge base.
     pub fn new() -> Self {
         KnowledgeBase {
             facts: HashSet::new(),
             pending: HashSet::new(),
             cache: HashMap::new(),
+            proofs: HashMap::new(),
+            stats: KbStats::default(),
         }
     }
 
+    /// Forgets every fact told.
     pub fn clear(&mut self) {
         self.facts.clear();
         self.pending.clear();
         self.invalidate_cache();
     }
 
+    /// Forgets the answers and proofs of past queries, to be
+    /// called whenever the facts they relied on changed.
     pub fn invalidate_cache(&mut self) {
         self.cache.clear();
+        self.proofs.clear();
+    }
+
+    /// Returns the statements that entailed `stmt` the last time
+    /// it was proven by `ask`. They are empty when `stmt` is a
+    /// fact on its own, and `None` when it wasn't proven.
+    pub fn premises(&self, stmt: &Statement) -> Option<&[Statement]> {
+        self.proofs.get(stmt).map(|premises| premises.as_slice())
+    }
+
+    /// The queries answered so far.
+    pub fn stats(&self) -> KbStats {
+        self.stats
     }
 
     /// Stores a fact into the knowledge base.
@@ -105,9 +148,28 @@ impl KnowledgeBase {
         self.invalidate_cache();
     }
 
+    /// Removes a fact told as is, such as a disjunction, from the
+    /// knowledge base. Facts split up by `tell` can't be retracted.
+    pub fn retract(&mut self, stmt: &Statement) {
+        if self.facts.remove(stmt) {
+            self.invalidate_cache();
+        }
+    }
+
+    /// Queries the knowledge base like `ask`, leaving the query and
+    /// the ones it leads to out of the stats. Meant for explaining
+    /// decisions rather than making them.
+    pub fn ask_uncounted(&mut self, stmt: &Statement) -> Option<bool> {
+        let stats = self.stats;
+        let answer = self.ask(stmt);
+        self.stats = stats;
+        answer
+    }
+
     /// Queries the knowledge base for a fact.
     pub fn ask(&mut self, stmt: &Statement) -> Option<bool> {
         /* Here, begins the real adventure of intelligent reasoning! */
+        self.stats.queries += 1;
         if self.pending.contains(stmt) {
             return None;
         }
@@ -115,6 +177,7 @@ impl KnowledgeBase {
         // println!("Asking truth for: {}", stmt);
         if self.cache.contains_key(stmt) {
             let cache_res = *self.cache.get(stmt).unwrap();
+            self.stats.cache_hits += 1;
             /* match cache_res {
                 true => println!("True: cache"),
                 false => println!("False: cache"),
@@ -123,6 +186,7 @@ impl KnowledgeBase {
         }
 
         self.pending.insert(stmt.clone());
+        let mut premises = Vec::new();
         let result = {
             let mut found_early = false;
             let mut early_res = false;
@@ -168,6 +232,7 @@ impl KnowledgeBase {
                             let answer = self.ask(&head);
                             if answer.is_some() && answer.unwrap() {
                                 entail_res = Some(true);
+                                premises = vec![head];
                                 break 'entail;
                             }
                         }
@@ -180,6 +245,8 @@ impl KnowledgeBase {
                                 }
                             }
                             entail_res = Some(true);
+                            premises = get_conjuncts(&head).into_iter().collect();
+                            premises.sort_by_key(|conj| conj.to_string());
                             break;
                         }
                         Statement::OrClause(_, _) => {
@@ -187,6 +254,7 @@ impl KnowledgeBase {
                                 let answer = self.ask(&disj);
                                 if answer.is_some() && answer.unwrap() {
                                     entail_res = Some(true);
+                                    premises = vec![disj];
                                     break 'entail;
                                 }
                             }
@@ -204,6 +272,9 @@ impl KnowledgeBase {
                 true => println!("True"),
                 false => println!("False"),
             } */
+            if result.unwrap() {
+                self.proofs.insert(stmt.clone(), premises);
+            }
             self.cache.insert(stmt.clone(), result.unwrap());
         } else {
             // println!("Cannot tell!")
@@ -213,6 +284,12 @@ impl KnowledgeBase {
     }
 }
 
+impl Default for KnowledgeBase {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 impl Display for KnowledgeBase {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         for fact in &self.facts {
@@ -263,3 +340,30 @@ fn get_disjuncts(stmt: &Statement) -> HashSet<Statement> {
 
     disjuncts
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn atom(symbol: &str) -> Statement {
+        Statement::Atomic(symbol.to_string())
+    }
+
+    #[test]
+    fn forgets_what_a_retracted_fact_entailed() {
+        let mut kb = KnowledgeBase::new();
+        let fact = atom("W_0,0");
+        kb.tell(Statement::ImplyClause(
+            fact.clone().boxed(),
+            atom("St_1,0").boxed(),
+        ));
+        kb.tell(fact.clone());
+        assert_eq!(kb.ask(&atom("St_1,0")), Some(true));
+        assert_eq!(kb.premises(&atom("St_1,0")), Some(&[fact.clone()][..]));
+
+        // The cached answer and its proof go along with the fact
+        kb.retract(&fact);
+        assert_eq!(kb.ask(&atom("St_1,0")), None);
+        assert_eq!(kb.premises(&atom("St_1,0")), None);
+    }
+}
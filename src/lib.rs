@@ -0,0 +1,66 @@
+//! The Wumpus World, as a library.
+//!
+//! The environment, the logic engine and knowledge base behind
+//! the knowledge-based agent, and the agents themselves, for
+//! tools building on the game. The interactive game is a binary
+//! on top of it.
+//!
+//! Fallible operations return the crate-wide `error::Error`.
+//!
+//! ```
+//! use wumpus_world::{
+//!     agent::Agent,
+//!     agents::AgentKind,
+//!     env::{Environment, GridType},
+//! };
+//!
+//! let mut env = Environment::new(GridType::Seeded(42));
+//! let mut agent = AgentKind::KnowledgeBased.build(&env)?;
+//! let mut observation = env.reset(None)?;
+//! agent.on_episode_start(&observation);
+//! loop {
+//!     let action = agent.act(&observation)?;
+//!     let step = env.step_with_reward(&action)?;
+//!     agent.on_result(&action, &step.info, step.reward);
+//!     if step.terminated || step.truncated {
+//!         break;
+//!     }
+//!     observation = step.observation;
+//! }
+//! # Ok::<(), wumpus_world::error::Error>(())
+//! ```
+
+#![warn(missing_docs)]
+
+/// The agent interface: actions, observations and decisions.
+pub mod agent;
+/// The built-in agents.
+pub mod agents;
+/// Ranking agents against each other on shared worlds.
+pub mod arena;
+/// Running agents over many episodes and reporting how they fare.
+pub mod bench;
+/// The environment agents play in.
+pub mod env;
+/// The crate-wide error type.
+pub mod error;
+/// The event log of a game session.
+pub mod events;
+/// Agents running as external processes.
+pub mod external;
+/// The grid of rooms and positions on it.
+pub mod grid;
+/// The knowledge base behind the knowledge-based agent.
+pub mod kb;
+/// Propositional logic statements and inference.
+pub mod logic;
+/// Loading and saving worlds as map files.
+pub mod map;
+/// Recording episodes and replaying them.
+pub mod replay;
+/// The rooms of the world.
+pub mod room;
+/// How actions are scored.
+pub mod scoring;
+/// Generating random worlds.
+pub mod world;
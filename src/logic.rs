@@ -4,21 +4,30 @@ use std::hash::Hash;
 type Left = Box<Statement>;
 type Right = Box<Statement>;
 
+/// A statement of propositional logic.
 #[derive(Eq, Hash, PartialEq, Clone, Debug)]
 pub enum Statement {
+    /// A symbol, true or false on its own
     Atomic(String),
+    /// Both statements hold
     AndClause(Left, Right),
+    /// Either statement holds
     OrClause(Left, Right),
+    /// The statement doesn't hold
     NotClause(Box<Statement>),
+    /// The right statement holds whenever the left one does
     ImplyClause(Left, Right),
+    /// Both statements hold or neither does
     EquivalClause(Left, Right),
 }
 
 impl Statement {
+    /// The statement on the heap, to build a clause from.
     pub fn boxed(self) -> Box<Self> {
         Box::new(self)
     }
 
+    /// The negation of the statement.
     pub fn negate(self) -> Self {
         Self::NotClause(self.boxed())
     }
@@ -1,16 +1,23 @@
-mod agent;
-mod agents;
-mod env;
-mod grid;
-mod kb;
-mod logic;
-mod room;
+mod cli;
 mod tui;
 
-use crate::{
+use std::{path::Path, time::Duration};
+
+use cli::{Args, Format, Mode, World};
+
+use wumpus_world::{
     agent::{Action, Agent, Direction},
-    agents::KnowledgeBasedAgent,
-    env::{ActionResult, Environment, GridType, Sense},
+    agents::{AgentKind, Contender, DEFAULT_MCTS_ITERATIONS},
+    arena::{self, ArenaConfig},
+    bench::{self, BenchConfig, BenchReport},
+    env::{ActionResult, Environment, GridType, Ruleset, Sense, Snapshot},
+    error::Error,
+    events::EventLog,
+    external,
+    kb::KbStats,
+    map::{self, Map},
+    replay::Replay,
+    scoring::{self, ScoringRules},
 };
 
 #[derive(PartialEq)]
@@ -20,6 +27,27 @@ enum GameMode {
 }
 
 fn main() {
+    let args = match cli::parse(std::env::args().skip(1)) {
+        Ok(args) => args,
+        Err(err) => {
+            eprintln!("Error: {}", err);
+            eprintln!();
+            eprintln!("{}", cli::USAGE);
+            std::process::exit(2);
+        }
+    };
+    if args.help {
+        println!("{}", cli::USAGE);
+        return;
+    }
+    if let Some(mode) = args.mode {
+        if let Err(err) = launch(mode, &args) {
+            eprintln!("Error: {}", err);
+            std::process::exit(1);
+        }
+        return;
+    }
+
     println!("Welcome to Wumpus World!");
 
     loop {
@@ -36,8 +64,19 @@ fn main() {
             "a" => {
                 play(GameMode::Agent);
             }
+            "r" => {
+                if let Err(err) = watch_replay(&tui::read_path("Replay file")) {
+                    tui::display_error(&err);
+                }
+            }
+            "n" => {
+                benchmark();
+            }
+            "v" => {
+                arena();
+            }
             "h" => {
-                tui::general_help();
+                tui::general_help(&ScoringRules::default());
             }
             "" => {}
             _ => {
@@ -47,200 +86,778 @@ fn main() {
     }
 }
 
+/// Launches straight into `mode`, set up by `args` rather than
+/// by prompts. Fails when the run can't be set up or breaks off,
+/// for the game to exit with an error.
+fn launch(mode: Mode, args: &Args) -> Result<(), Error> {
+    let log = args.log.as_ref().map(EventLog::create).transpose()?;
+    let env = match &args.world {
+        World::Classic => Environment::new(GridType::Classic),
+        World::Random => Environment::new(match args.seed {
+            Some(seed) => GridType::Seeded(seed),
+            None => GridType::Random,
+        }),
+        World::Map(path) => Map::load(path)?.into_environment(),
+    }
+    .with_ruleset(args.ruleset);
+    // Nobody is there to stop an agent going in circles, and
+    // benchmarks and arenas default to a limit of their own
+    let step_limit = match mode {
+        Mode::Agent => args.max_steps.or(Some(scoring::default_step_limit(
+            env.grid().nrows(),
+            env.grid().ncols(),
+        ))),
+        _ => args.max_steps,
+    };
+    let env = env.with_scoring(ScoringRules {
+        step_limit,
+        repeat_limit: args.max_repeats,
+        ..ScoringRules::preset(args.ruleset)
+    });
+
+    match mode {
+        Mode::Play => play_as_player(env, log),
+        Mode::Agent => {
+            let run = AgentRun {
+                agent: args.agent.clone(),
+                explain: false,
+                replay_path: None,
+                delay: args.delay,
+                format: args.format,
+                interactive: false,
+            };
+            run_agent(env, log, &run)?;
+        }
+        Mode::Bench => {
+            let mut config = BenchConfig::new(args.agent.clone(), args.episodes);
+            config.first_seed = args.seed.unwrap_or(config.first_seed);
+            config.threads = args.threads;
+            config.ruleset = args.ruleset;
+            config.scoring = ScoringRules {
+                step_limit: args.max_steps.or(config.scoring.step_limit),
+                ..env.scoring().clone()
+            };
+            let report = run_benchmark(&config, log, args.format)?;
+            let mut out = std::io::stdout();
+            match args.format {
+                Format::Text => tui::display_bench_report(&report),
+                Format::Json => report.write_json(&mut out).map_err(Error::Io)?,
+                Format::Csv => report.write_csv(&mut out).map_err(Error::Io)?,
+            }
+        }
+        Mode::Arena => {
+            let mut config = ArenaConfig::new(args.episodes);
+            // Only external agents play by the AIMA ruleset
+            config.agents = AgentKind::registered()
+                .into_iter()
+                .chain(args.external.clone())
+                .filter(|agent| agent.supports(args.ruleset))
+                .map(Contender::from)
+                .collect();
+            config.first_seed = args.seed.unwrap_or(config.first_seed);
+            config.threads = args.threads;
+            config.ruleset = args.ruleset;
+            config.scoring = ScoringRules {
+                step_limit: args.max_steps.or(config.scoring.step_limit),
+                ..env.scoring().clone()
+            };
+            let report = run_arena(&config, args.format)?;
+            let mut out = std::io::stdout();
+            match args.format {
+                Format::Text => tui::display_arena_report(&report, DIVERGENT_WORLDS),
+                Format::Json => report.write_json(&mut out).map_err(Error::Io)?,
+                Format::Csv => report.write_csv(&mut out).map_err(Error::Io)?,
+            }
+        }
+        Mode::Replay => {
+            if let Some(path) = &args.replay {
+                watch_replay(path)?;
+            }
+        }
+    }
+    Ok(())
+}
+
 fn play(mode: GameMode) {
-    let grid_type: GridType;
-    loop {
+    let mut env = loop {
         println!("Choose grid variant: ");
         println!();
         println!("[c] Classic");
         println!("[r] Random");
+        println!("[x] Custom Random");
+        println!("[f] Map File");
         println!();
         println!("[b] Back");
         tui::print_prompt();
         match tui::read_command().as_str() {
-            "c" => {
-                grid_type = GridType::Classic;
-                break;
-            }
+            "c" => break Environment::new(GridType::Classic),
             "r" => {
-                grid_type = GridType::Random;
-                break;
+                break Environment::new(match tui::read_seed() {
+                    Some(seed) => GridType::Seeded(seed),
+                    None => GridType::Random,
+                });
             }
+            "x" => {
+                let config = tui::read_world_config();
+                let seed = tui::read_seed().unwrap_or_else(rand::random);
+                match Environment::generate(&config, seed) {
+                    Ok(env) => break env,
+                    Err(err) => tui::display_error(&err),
+                }
+            }
+            "f" => match Map::load(tui::read_path("Map file")) {
+                Ok(map) => {
+                    if let Some(name) = map.get("name") {
+                        println!("Loaded map: {}", name);
+                    }
+                    break map.into_environment();
+                }
+                Err(err) => tui::display_error(&err),
+            },
             "b" => return,
             _ => {}
         }
+    };
+
+    let Some(ruleset) = tui::read_ruleset() else {
+        return;
+    };
+    env = env
+        .with_ruleset(ruleset)
+        .with_scoring(tui::read_scoring(ruleset));
+    let log = match tui::read_output_path("Log events to").map(EventLog::create) {
+        Some(Ok(log)) => Some(log),
+        Some(Err(err)) => {
+            tui::display_error(&err);
+            return;
+        }
+        None => None,
+    };
+
+    match mode {
+        GameMode::Player => play_as_player(env, log),
+        GameMode::Agent => {
+            let Some(agent) = choose_agent() else {
+                return;
+            };
+            let run = AgentRun {
+                agent,
+                explain: tui::yes_no("Show decision explanations?"),
+                replay_path: tui::read_output_path("Save replays to"),
+                delay: Duration::ZERO,
+                format: Format::Text,
+                interactive: true,
+            };
+            if let Err(err) = run_agent(env, log, &run) {
+                tui::display_error(&err);
+            }
+        }
     }
+}
 
+/// Lets the player play `env` until they go back to the menu.
+fn play_as_player(mut env: Environment, log: Option<EventLog>) {
     println!();
     println!("Initializing game..");
+    if let Some(seed) = env.seed() {
+        tui::display_seed(seed);
+    }
 
-    let mut env = Environment::new(grid_type);
+    let mut session = Session::new(&env, log);
+    tui::play_help(env.ruleset());
+    loop {
+        tui::print_prompt();
 
-    match mode {
-        GameMode::Player => {
-            tui::play_help();
-            loop {
-                tui::print_prompt();
-
-                let mut direction: Option<Direction>;
-                match tui::read_command().as_str() {
-                    "b" => {
-                        if tui::confirm() {
-                            break;
+        let mut direction: Option<Direction>;
+        match tui::read_command().as_str() {
+            "b" => {
+                if tui::confirm() {
+                    break;
+                }
+            }
+            "?" => {
+                tui::play_help(env.ruleset());
+            }
+            "" => {}
+            "g?" => {
+                tui::display_env(&env);
+            }
+            "s?" => {
+                tui::display_score(env.score());
+                tui::display_inventory(env.arrows(), env.gold());
+            }
+            "r?" => {
+                tui::display_rules(env.scoring());
+            }
+            "u" => match session.undo(&mut env) {
+                true => tui::display_env(&env),
+                false => println!("Nothing to undo!"),
+            },
+            save if save.starts_with("sr ") => {
+                let path = save["sr ".len()..].trim();
+                match session.replay.save(path) {
+                    Ok(()) => println!("Replay saved to {}", path),
+                    Err(err) => tui::display_error(&err),
+                }
+            }
+            save if save.starts_with("sv ") => {
+                let path = save["sv ".len()..].trim();
+                match map::save(&env, path) {
+                    Ok(()) => println!("Map saved to {}", path),
+                    Err(err) => tui::display_error(&err),
+                }
+            }
+            mov if mov.starts_with("mv ") && {
+                direction = tui::parse_direction(mov);
+                direction.is_some()
+            } =>
+            {
+                let action = Action::Move(direction.unwrap());
+                if !is_allowed(&env, &action) {
+                    continue;
+                }
+                match session.step(&mut env, &action) {
+                    Ok(ActionResult::Ok) => tui::display_env(&env),
+                    Ok(ActionResult::GameOver) => {
+                        tui::display_env(&env);
+                        match tui::game_over(&env) {
+                            true => break,
+                            false => {
+                                session.restart(&mut env);
+                                tui::play_help(env.ruleset());
+                            }
                         }
                     }
-                    "?" => {
-                        tui::play_help();
-                    }
-                    "" => {}
-                    "g?" => {
+                    Ok(ActionResult::Sense(Sense::Bump(_))) => tui::invalid_direction(),
+                    Ok(_) => {}
+                    Err(err) => tui::display_error(&err),
+                };
+            }
+            shoot
+                if shoot.starts_with("sh ") && {
+                    direction = tui::parse_direction(shoot);
+                    direction.is_some()
+                } =>
+            {
+                let action = Action::Shoot(direction.unwrap());
+                if !is_allowed(&env, &action) {
+                    continue;
+                }
+                match session.step(&mut env, &action) {
+                    Ok(ActionResult::Sense(Sense::Scream)) => {
                         tui::display_env(&env);
+                        println!("You killed the Wumpus!");
                     }
-                    "s?" => {
-                        tui::display_score(env.score());
+                    Ok(ActionResult::Ok) => println!("Your arrow missed."),
+                    Ok(ActionResult::OutOfArrows) => println!("You have no arrow left!"),
+                    Ok(ActionResult::GameOver) => {
+                        tui::display_env(&env);
+                        match tui::game_over(&env) {
+                            true => break,
+                            false => {
+                                session.restart(&mut env);
+                                tui::play_help(env.ruleset());
+                            }
+                        }
                     }
-                    mov if mov.starts_with("mv ") && {
-                        direction = tui::parse_direction(mov);
-                        direction.is_some()
-                    } =>
-                    {
-                        match env.step(&Action::Move(direction.unwrap())) {
-                            ActionResult::Ok => tui::display_env(&env),
-                            ActionResult::GameOver => {
-                                tui::display_env(&env);
-                                match tui::game_over(env.score()) {
-                                    true => break,
-                                    false => {
-                                        env.initialize();
-                                        tui::play_help();
-                                    }
-                                }
+                    Ok(_) => {}
+                    Err(err) => tui::display_error(&err),
+                };
+            }
+            cmd @ ("fw" | "tl" | "tr" | "sh") => {
+                let action = match cmd {
+                    "fw" => Action::Forward,
+                    "tl" => Action::TurnLeft,
+                    "tr" => Action::TurnRight,
+                    _ => Action::ShootForward,
+                };
+                if !is_allowed(&env, &action) {
+                    continue;
+                }
+                match session.step(&mut env, &action) {
+                    Ok(ActionResult::Ok) => match action {
+                        Action::Forward => tui::display_env(&env),
+                        Action::ShootForward => println!("Your arrow missed."),
+                        _ => {
+                            if let Some(orientation) = env.observation().orientation() {
+                                tui::display_orientation(orientation);
+                            }
+                        }
+                    },
+                    Ok(ActionResult::GameOver) => {
+                        tui::display_env(&env);
+                        match tui::game_over(&env) {
+                            true => break,
+                            false => {
+                                session.restart(&mut env);
+                                tui::play_help(env.ruleset());
+                                continue;
                             }
-                            ActionResult::Sense(obs) => match obs {
-                                Sense::Bump(_) => tui::invalid_direction(),
-                                _ => fatal(),
-                            },
-                            _ => fatal(),
-                        };
+                        }
+                    }
+                    Ok(ActionResult::Sense(Sense::Bump(_))) => {
+                        println!("You bumped into a wall!")
                     }
-                    shoot
-                        if shoot.starts_with("sh ") && {
-                            direction = tui::parse_direction(shoot);
-                            direction.is_some()
-                        } =>
-                    {
-                        match env.step(&Action::Shoot(direction.unwrap())) {
-                            ActionResult::Sense(obs) => match obs {
-                                Sense::Scream(_) => {
-                                    tui::display_env(&env);
-                                    println!("You killed the Wumpus!");
-                                }
-                                Sense::Bump(_) => tui::invalid_direction(),
-                                _ => fatal(),
-                            },
-                            ActionResult::Ok => {}
-                            _ => fatal(),
-                        };
+                    Ok(ActionResult::Sense(Sense::Scream)) => {
+                        tui::display_env(&env);
+                        println!("You killed the Wumpus!");
                     }
-                    "cl" => {
-                        match env.step(&Action::Climb) {
-                            ActionResult::Sense(obs) => match obs {
-                                Sense::Ceil => println!("Cannot climb from here!"),
-                                _ => fatal(),
-                            },
-                            ActionResult::GameOver => match tui::game_over(env.score()) {
-                                true => break,
-                                false => {
-                                    env.initialize();
-                                    tui::play_help();
-                                }
-                            },
-                            _ => fatal(),
-                        };
+                    Ok(ActionResult::OutOfArrows) => println!("You have no arrow left!"),
+                    Ok(_) => {}
+                    Err(err) => tui::display_error(&err),
+                };
+                tui::display_percept(&env.observation().percept());
+            }
+            "cl" => {
+                match session.step(&mut env, &Action::Climb) {
+                    Ok(ActionResult::Sense(Sense::Ceil)) => {
+                        println!("Cannot climb from here!")
                     }
-                    "gr" => match env.step(&Action::Grab) {
-                        ActionResult::Grabbed(item) => {
-                            tui::display_env(&env);
-                            println!("You grabbed {:?}", item);
+                    Ok(ActionResult::GameOver) => match tui::game_over(&env) {
+                        true => break,
+                        false => {
+                            session.restart(&mut env);
+                            tui::play_help(env.ruleset());
                         }
-                        ActionResult::Ok => println!("Nothing can be grabbed here!"),
-                        _ => fatal(),
                     },
-                    _ => tui::invalid_input(),
-                }
+                    Ok(_) => {}
+                    Err(err) => tui::display_error(&err),
+                };
+            }
+            "gr" => {
+                match session.step(&mut env, &Action::Grab) {
+                    Ok(ActionResult::Grabbed(item)) => {
+                        tui::display_env(&env);
+                        println!("You grabbed {:?}", item);
+                    }
+                    Ok(ActionResult::Ok) => println!("Nothing can be grabbed here!"),
+                    Ok(ActionResult::GameOver) => {
+                        tui::display_env(&env);
+                        match tui::game_over(&env) {
+                            true => break,
+                            false => {
+                                session.restart(&mut env);
+                                tui::play_help(env.ruleset());
+                            }
+                        }
+                    }
+                    Ok(_) => {}
+                    Err(err) => tui::display_error(&err),
+                };
             }
+            _ => tui::invalid_input(),
         }
-        GameMode::Agent => {
+    }
+}
+
+/// How an agent run is set up.
+///
+/// ## Fields
+///
+/// * `agent` - The agent to run
+/// * `explain` - Whether to show the agent's decisions
+/// * `replay_path` - Where to save the replay of each episode,
+///   numbered after the episode when the run is interactive
+/// * `delay` - Pause between the agent's steps
+/// * `format` - How the results are reported
+/// * `interactive` - Whether to offer another episode once one
+///   is over, rather than stopping there
+struct AgentRun {
+    agent: AgentKind,
+    explain: bool,
+    replay_path: Option<String>,
+    delay: Duration,
+    format: Format,
+    interactive: bool,
+}
+
+/// Lets an agent play `env` as set up by `run`. Fails when the
+/// agent can't be built, or breaks off an episode.
+fn run_agent(mut env: Environment, mut log: Option<EventLog>, run: &AgentRun) -> Result<(), Error> {
+    let text = run.format == Format::Text;
+    if text {
+        println!();
+        println!("Initializing game..");
+        if let Some(seed) = env.seed() {
+            tui::display_seed(seed);
+        }
+        println!();
+        tui::display_env(&env);
+        println!();
+    }
+
+    let mut agent = run.agent.build(&env)?;
+    if run.format == Format::Csv {
+        println!("{}", bench::CSV_HEADER);
+    }
+
+    let mut episode = 0;
+    loop {
+        env.reset(None)?;
+        if episode > 0 {
             println!();
             tui::display_env(&env);
             println!();
+        }
+        episode += 1;
+        let mut replay = Replay::new(&env);
+        if let Some(log) = &mut log {
+            log.start_episode();
+        }
 
-            let mut agent = KnowledgeBasedAgent::new(
-                env.agent_position(),
-                env.grid().nrows(),
-                env.grid().ncols(),
-            );
-
-            loop {
-                let action = agent.act(env.observation());
-                match &action {
-                    Action::Move(_) => match env.step(&action) {
-                        ActionResult::Ok => {
-                            tui::display_env(&env);
-                            println!();
-                        }
-                        ActionResult::GameOver => {
-                            tui::display_env(&env);
-                            match tui::game_over(env.score()) {
-                                true => break,
-                                false => {
-                                    env.initialize();
-                                    agent.reset();
-                                    println!();
-                                    tui::display_env(&env);
-                                    println!();
-                                }
-                            }
-                        }
-                        _ => {}
-                    },
-                    Action::Shoot(_) => match env.step(&action) {
-                        ActionResult::Sense(obs) => match obs {
-                            Sense::Scream(_) => {
-                                tui::display_env(&env);
-                                println!("The Wumpus is killed!");
-                            }
-                            _ => {}
-                        },
-                        _ => {}
-                    },
-                    Action::Climb => match env.step(&action) {
-                        ActionResult::GameOver => match tui::game_over(env.score()) {
-                            true => break,
-                            false => {
-                                env.initialize();
-                                agent.reset();
-                                println!();
-                                tui::display_env(&env);
-                                println!();
-                            }
-                        },
-                        _ => {}
-                    },
-                    Action::Grab => match env.step(&action) {
-                        ActionResult::Grabbed(item) => {
-                            tui::display_env(&env);
-                            println!("Agent grabbed {:?}", item);
-                            println!();
-                        }
-                        _ => fatal(),
-                    },
+        let played = bench::play_episode(&mut env, agent.as_mut(), |env, agent, step| {
+            if text
+                && run.explain
+                && let Some(decision) = agent.explain()
+            {
+                tui::display_decision(decision);
+            }
+            replay.record(&step.action, &step.result.info, env.score());
+            log_step(&mut log, env, &step.action, &step.result.info, step.kb);
+            if step.result.terminated || step.result.truncated {
+                return Ok(());
+            }
+            if text {
+                display_step(env, agent, &step.action, &step.result.info);
+            }
+            if !run.delay.is_zero() {
+                std::thread::sleep(run.delay);
+            }
+            Ok(())
+        });
+        let report = played?;
+
+        if text {
+            display_agent_env(&env, agent.as_mut());
+        }
+        if let Some(path) = &run.replay_path {
+            let path = match run.interactive {
+                true => episode_path(path, episode),
+                false => path.clone(),
+            };
+            match replay.save(&path) {
+                Ok(()) => println!("Replay saved to {}", path),
+                Err(err) => tui::display_error(&err),
+            }
+        }
+        let done = match run.format {
+            Format::Text if run.interactive => tui::game_over(&env),
+            Format::Text => {
+                tui::display_summary(&env);
+                true
+            }
+            Format::Json | Format::Csv => {
+                let mut out = std::io::stdout();
+                match run.format {
+                    Format::Json => report.write_json(&mut out),
+                    _ => report.write_csv(&mut out),
                 }
+                .map_err(Error::Io)?;
+                true
+            }
+        };
+        if done {
+            return Ok(());
+        }
+    }
+}
+
+/// `path` with `episode` appended to the file name, before its
+/// extension: `runs/game.json` becomes `runs/game-2.json`.
+fn episode_path(path: &str, episode: usize) -> String {
+    let path = Path::new(path);
+    let stem = path.file_stem().unwrap_or_default().to_string_lossy();
+    let name = match path.extension() {
+        Some(ext) => format!("{}-{}.{}", stem, episode, ext.to_string_lossy()),
+        None => format!("{}-{}", stem, episode),
+    };
+    path.with_file_name(name).to_string_lossy().into_owned()
+}
+
+/// Shows what `action` did to `env` when it didn't end the
+/// episode.
+fn display_step(env: &Environment, agent: &mut dyn Agent, action: &Action, result: &ActionResult) {
+    match result {
+        ActionResult::Ok => match action {
+            Action::Move(_) | Action::Forward => {
+                display_agent_env(env, agent);
+                println!();
+            }
+            Action::Shoot(_) | Action::ShootForward => println!("Agent's arrow missed."),
+            _ => {}
+        },
+        ActionResult::Sense(Sense::Scream) => {
+            display_agent_env(env, agent);
+            println!("The Wumpus is killed!");
+        }
+        ActionResult::Sense(Sense::Bump(_)) => println!("Agent bumped into a wall."),
+        ActionResult::OutOfArrows => println!("Agent has no arrow left."),
+        ActionResult::Sense(_) | ActionResult::GameOver => {}
+        ActionResult::Grabbed(item) => {
+            display_agent_env(env, agent);
+            println!("Agent grabbed {:?}", item);
+            println!();
+        }
+    }
+}
+
+/// Loads a replay, checks that it plays out as recorded, and
+/// lets the user step through it. Fails when the replay can't be
+/// loaded or doesn't play out as recorded.
+fn watch_replay(path: &str) -> Result<(), Error> {
+    let replay = Replay::load(path)?;
+    let snapshots = replay.verify()?;
+    println!("Replay verified, {} steps", replay.steps().len());
+
+    let mut env = replay.environment();
+    let mut index = 0;
+    tui::replay_help();
+    loop {
+        env.restore(&snapshots[index]);
+        println!();
+        tui::display_env(&env);
+        match index.checked_sub(1) {
+            Some(i) => tui::display_replay_step(index, &replay.steps()[i]),
+            None => println!("Start of the episode"),
+        }
+        tui::print_prompt();
+
+        match tui::read_command().as_str() {
+            "n" | "" if index + 1 < snapshots.len() => index += 1,
+            "p" if index > 0 => index -= 1,
+            "n" | "" | "p" => println!("No more steps that way!"),
+            "?" => tui::replay_help(),
+            "b" => return Ok(()),
+            _ => tui::invalid_input(),
+        }
+    }
+}
+
+/// Runs an agent over a batch of random worlds without
+/// displaying them, then reports how it did.
+fn benchmark() {
+    let Some(agent) = choose_agent() else {
+        return;
+    };
+    let mut config = BenchConfig::new(agent, tui::read_number("Episodes", 100));
+    config.first_seed = tui::read_number("First seed", 1) as u64;
+    config.threads = tui::read_number("Threads", 1);
+    let Some(ruleset) = tui::read_ruleset() else {
+        return;
+    };
+    config.ruleset = ruleset;
+    config.scoring = tui::read_scoring(ruleset);
+    let csv_path = tui::read_output_path("Save results as CSV to");
+    let log = match tui::read_output_path("Log events to").map(EventLog::create) {
+        Some(Ok(log)) => Some(log),
+        Some(Err(err)) => {
+            tui::display_error(&err);
+            return;
+        }
+        None => None,
+    };
+
+    let report = match run_benchmark(&config, log, Format::Text) {
+        Ok(report) => report,
+        Err(err) => {
+            tui::display_error(&err);
+            return;
+        }
+    };
+    tui::display_bench_report(&report);
+
+    if let Some(path) = csv_path {
+        let written = std::fs::File::create(&path)
+            .and_then(|file| report.write_csv(&mut std::io::BufWriter::new(file)));
+        match written {
+            Ok(()) => println!("Results saved to {}", path),
+            Err(err) => tui::display_error(&err),
+        }
+    }
+}
+
+/// Runs every agent over the same batch of random worlds, then
+/// compares how they did.
+fn arena() {
+    let mut config = ArenaConfig::new(tui::read_number("Episodes", 100));
+    config.first_seed = tui::read_number("First seed", 1) as u64;
+    config.threads = tui::read_number("Threads", 1);
+    config.scoring = tui::read_scoring(Ruleset::Absolute);
+    match run_arena(&config, Format::Text) {
+        Ok(report) => {
+            println!();
+            tui::display_arena_report(&report, DIVERGENT_WORLDS);
+        }
+        Err(err) => tui::display_error(&err),
+    }
+}
+
+/// Number of worlds the agents disagreed on the most shown after
+/// an arena.
+const DIVERGENT_WORLDS: usize = 5;
+
+/// Runs the arena of `config`, telling the user when its results
+/// are reported as text.
+fn run_arena(config: &ArenaConfig, format: Format) -> Result<arena::ArenaReport, Error> {
+    if format == Format::Text {
+        println!();
+        println!(
+            "Running {} agents over {} worlds..",
+            config.agents.len(),
+            config.episodes
+        );
+    }
+    arena::run(config)
+}
+
+/// Runs the benchmark of `config`, telling the user when its
+/// results are reported as text.
+fn run_benchmark(
+    config: &BenchConfig,
+    log: Option<EventLog>,
+    format: Format,
+) -> Result<BenchReport, Error> {
+    if format == Format::Text {
+        println!();
+        println!("Running {} episodes..", config.episodes);
+    }
+    bench::run(config, log)
+}
+
+/// Displays the environment, beside the agent's beliefs when
+/// it keeps any.
+fn display_agent_env(env: &Environment, agent: &mut dyn Agent) {
+    match agent.beliefs() {
+        Some(beliefs) => tui::display_env_with_beliefs(env, &beliefs),
+        None => tui::display_env(env),
+    }
+}
+
+/// What player mode keeps track of beside the environment.
+struct Session {
+    /// Snapshots taken before each action, to undo it
+    history: Vec<Snapshot>,
+    replay: Replay,
+    log: Option<EventLog>,
+}
+
+impl Session {
+    fn new(env: &Environment, mut log: Option<EventLog>) -> Self {
+        if let Some(log) = &mut log {
+            log.start_episode();
+        }
+        Self {
+            history: Vec::new(),
+            replay: Replay::new(env),
+            log,
+        }
+    }
+
+    /// Takes `action`, keeping a snapshot to undo it, recording
+    /// it in the replay and logging it. A step cutting the episode
+    /// short is recorded with the result of its action, but
+    /// returned as `ActionResult::GameOver`.
+    fn step(&mut self, env: &mut Environment, action: &Action) -> Result<ActionResult, Error> {
+        let snapshot = env.snapshot();
+        let step = env.step_with_reward(action)?;
+        self.history.push(snapshot);
+        self.replay.record(action, &step.info, env.score());
+        log_step(&mut self.log, env, action, &step.info, None);
+        Ok(match step.truncated {
+            true => ActionResult::GameOver,
+            false => step.info,
+        })
+    }
+
+    /// Takes back the last action, dropping it from the replay and
+    /// logging the undo. Returns `false` when there is none.
+    fn undo(&mut self, env: &mut Environment) -> bool {
+        let Some(snapshot) = self.history.pop() else {
+            return false;
+        };
+        env.restore(&snapshot);
+        self.replay.truncate(env.steps());
+        if let Some(events) = &mut self.log
+            && let Err(err) = events.record_undo(env)
+        {
+            tui::display_error(&err);
+            self.log = None;
+        }
+        true
+    }
+
+    /// Starts the episode over, forgetting its history.
+    fn restart(&mut self, env: &mut Environment) {
+        env.initialize();
+        *self = Self::new(env, self.log.take());
+    }
+}
+
+/// Logs a step to `log`, if any. A log that can't be written to
+/// is reported and dropped, without interrupting the game.
+fn log_step(
+    log: &mut Option<EventLog>,
+    env: &Environment,
+    action: &Action,
+    result: &ActionResult,
+    kb: Option<KbStats>,
+) {
+    if let Some(events) = log
+        && let Err(err) = events.record(env, action, result, kb)
+    {
+        tui::display_error(&err);
+        *log = None;
+    }
+}
+
+fn choose_agent() -> Option<AgentKind> {
+    loop {
+        println!("Choose agent: ");
+        println!();
+        println!("[k] Knowledge-Based");
+        println!("[u] Knowledge-Based (unknown map size)");
+        println!("[m] Monte Carlo (POMCP)");
+        println!("[x] External Program");
+        println!();
+        println!("[b] Back");
+        tui::print_prompt();
+        match tui::read_command().as_str() {
+            "k" => return Some(AgentKind::KnowledgeBased),
+            "u" => return Some(AgentKind::KnowledgeBasedUnknownSize),
+            "m" => {
+                let iterations = tui::read_number("Iterations per step", DEFAULT_MCTS_ITERATIONS);
+                return Some(AgentKind::MonteCarlo { iterations });
+            }
+            "x" => {
+                let (program, args) = match external::split_command(&tui::read_path("Command")) {
+                    Ok(command) => command,
+                    Err(err) => {
+                        tui::display_error(&err);
+                        continue;
+                    }
+                };
+                let timeout = tui::read_number(
+                    "Reply timeout (ms)",
+                    external::DEFAULT_TIMEOUT.as_millis() as usize,
+                );
+                return Some(AgentKind::External {
+                    program,
+                    args,
+                    timeout: Duration::from_millis(timeout as u64),
+                });
             }
+            "b" => return None,
+            _ => {}
         }
     }
 }
 
-fn fatal() {
-    panic!("Fatal error!")
+/// Whether `action` can be taken under the ruleset of `env`,
+/// telling the player when it can't.
+fn is_allowed(env: &Environment, action: &Action) -> bool {
+    let allowed = env.ruleset().allows(action);
+    if !allowed {
+        tui::not_in_ruleset(env.ruleset());
+    }
+    allowed
 }
@@ -0,0 +1,438 @@
+use std::{
+    fmt::{Display, Write},
+    path::Path,
+};
+
+use crate::{
+    env::Environment,
+    error::Error,
+    grid::{Grid, Pos, Violation},
+    room::RoomKind,
+};
+
+/// Glyph marking the start room, which is otherwise empty.
+const START_GLYPH: char = 'A';
+const COMMENT_GLYPH: char = '#';
+
+/// A world in the plain-text map format.
+///
+/// ## Format
+///
+/// An optional header of `key: value` metadata lines, followed
+/// by the grid, one line per row. Each room is written with the
+/// same glyph as `RoomKind`'s `Display` (`_`, `P`, `W` or `G`),
+/// and the start room with `A`. Whitespace between rooms is
+/// ignored, and lines starting with `#` are comments.
+///
+/// ```text
+/// # The classic layout
+/// name: Classic
+///
+/// _ _ _ P
+/// W G P _
+/// _ _ _ _
+/// A _ P _
+/// ```
+#[derive(Clone, Debug)]
+pub struct Map {
+    grid: Grid,
+    start: Pos,
+    metadata: Vec<(String, String)>,
+}
+
+/// What went wrong while parsing a map.
+#[derive(Debug, Clone, PartialEq)]
+pub enum ParseErrorKind {
+    /// A room glyph that isn't one of `_ P W G A`
+    UnknownGlyph(char),
+    /// A row with a different number of rooms than the first one
+    RaggedRow {
+        /// Rooms in the first row
+        expected: usize,
+        /// Rooms in this row
+        found: usize,
+    },
+    /// More than one start marker
+    DuplicateStart,
+    /// A header line that isn't a `key: value` pair
+    MalformedHeader,
+}
+
+impl Display for ParseErrorKind {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ParseErrorKind::UnknownGlyph(glyph) => {
+                f.write_fmt(format_args!("unknown room glyph '{}'", glyph))
+            }
+            ParseErrorKind::RaggedRow { expected, found } => f.write_fmt(format_args!(
+                "row has {} rooms, expected {}",
+                found, expected
+            )),
+            ParseErrorKind::DuplicateStart => f.write_str("start marker appears more than once"),
+            ParseErrorKind::MalformedHeader => f.write_str("expected a `key: value` header line"),
+        }
+    }
+}
+
+/// Errors raised while loading or saving a map.
+#[derive(Debug)]
+pub enum MapError {
+    /// The map file couldn't be read or written
+    Io(std::io::Error),
+    /// A malformed line, at the given 1-based line and column
+    Parse {
+        /// The 1-based line
+        line: usize,
+        /// The 1-based column
+        col: usize,
+        /// What is wrong with it
+        kind: ParseErrorKind,
+    },
+    /// The map has a header but no grid
+    EmptyGrid,
+    /// The grid has no start marker
+    MissingStart,
+    /// The world breaks some rules of the game
+    Invalid(Vec<Violation>),
+}
+
+impl Display for MapError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            MapError::Io(err) => err.fmt(f),
+            MapError::Parse { line, col, kind } => {
+                f.write_fmt(format_args!("line {}, column {}: {}", line, col, kind))
+            }
+            MapError::EmptyGrid => f.write_str("map has no grid"),
+            MapError::MissingStart => {
+                f.write_fmt(format_args!("map has no start marker '{}'", START_GLYPH))
+            }
+            MapError::Invalid(violations) => f.write_fmt(format_args!(
+                "map breaks the rules: {}",
+                violations
+                    .iter()
+                    .map(|v| v.to_string())
+                    .collect::<Vec<_>>()
+                    .join("; ")
+            )),
+        }
+    }
+}
+
+impl std::error::Error for MapError {}
+
+impl From<std::io::Error> for MapError {
+    fn from(err: std::io::Error) -> Self {
+        MapError::Io(err)
+    }
+}
+
+impl Map {
+    /// Captures the world of `env` as it was laid out, before any
+    /// gold was grabbed or Wumpus killed. The header of the map
+    /// it was loaded from is kept, and its seed is recorded when
+    /// it was generated from one.
+    pub fn from_env(env: &Environment) -> Self {
+        let mut metadata: Vec<(String, String)> = env
+            .metadata()
+            .iter()
+            .filter(|(key, _)| key != "seed")
+            .cloned()
+            .collect();
+        if let Some(seed) = env.seed() {
+            metadata.push(("seed".to_string(), seed.to_string()));
+        }
+
+        Self {
+            grid: env.layout().clone(),
+            start: env.start_position().clone(),
+            metadata,
+        }
+    }
+
+    /// Reads and parses the map file at `path`.
+    pub fn load(path: impl AsRef<Path>) -> Result<Self, Error> {
+        let text = std::fs::read_to_string(path).map_err(MapError::Io)?;
+        Self::parse(&text)
+    }
+
+    /// Parses a map, then validates the world it describes
+    /// against the rules of the game.
+    pub fn parse(text: &str) -> Result<Self, Error> {
+        Ok(Self::parse_map(text)?)
+    }
+
+    fn parse_map(text: &str) -> Result<Self, MapError> {
+        let mut metadata = Vec::new();
+        let mut kinds: Vec<Vec<RoomKind>> = Vec::new();
+        let mut start: Option<Pos> = None;
+
+        for (index, line) in text.lines().enumerate() {
+            let line_no = index + 1;
+            let trimmed = line.trim();
+            if trimmed.is_empty() || trimmed.starts_with(COMMENT_GLYPH) {
+                continue;
+            }
+
+            // Header lines only come before the grid
+            if kinds.is_empty() && trimmed.contains(':') {
+                let (key, value) = trimmed.split_once(':').unwrap();
+                if key.trim().is_empty() || key.trim().contains(char::is_whitespace) {
+                    return Err(MapError::Parse {
+                        line: line_no,
+                        col: line.find(|c: char| !c.is_whitespace()).unwrap() + 1,
+                        kind: ParseErrorKind::MalformedHeader,
+                    });
+                }
+                metadata.push((key.trim().to_string(), value.trim().to_string()));
+                continue;
+            }
+
+            let mut row = Vec::new();
+            for (col, glyph) in line.chars().enumerate() {
+                if glyph.is_whitespace() {
+                    continue;
+                }
+                let kind = match glyph {
+                    '_' => RoomKind::Void,
+                    'P' => RoomKind::Pit,
+                    'W' => RoomKind::Wumpus,
+                    'G' => RoomKind::Gold,
+                    START_GLYPH => {
+                        if start.is_some() {
+                            return Err(MapError::Parse {
+                                line: line_no,
+                                col: col + 1,
+                                kind: ParseErrorKind::DuplicateStart,
+                            });
+                        }
+                        start = Some(Pos::new(kinds.len(), row.len()));
+                        RoomKind::Void
+                    }
+                    _ => {
+                        return Err(MapError::Parse {
+                            line: line_no,
+                            col: col + 1,
+                            kind: ParseErrorKind::UnknownGlyph(glyph),
+                        });
+                    }
+                };
+                row.push(kind);
+            }
+
+            if let Some(first) = kinds.first()
+                && first.len() != row.len()
+            {
+                return Err(MapError::Parse {
+                    line: line_no,
+                    col: line.trim_end().len() + 1,
+                    kind: ParseErrorKind::RaggedRow {
+                        expected: first.len(),
+                        found: row.len(),
+                    },
+                });
+            }
+            kinds.push(row);
+        }
+
+        if kinds.is_empty() {
+            return Err(MapError::EmptyGrid);
+        }
+        let Some(start) = start else {
+            return Err(MapError::MissingStart);
+        };
+
+        let mut grid = Grid::new(kinds.len(), kinds[0].len());
+        for (i, row) in kinds.into_iter().enumerate() {
+            for (j, kind) in row.into_iter().enumerate() {
+                grid[i][j].set_kind(kind);
+            }
+        }
+
+        grid.validate(&start).map_err(MapError::Invalid)?;
+
+        Ok(Self {
+            grid,
+            start,
+            metadata,
+        })
+    }
+
+    /// Returns the value of the header `key`, if present.
+    pub fn get(&self, key: &str) -> Option<&str> {
+        self.metadata
+            .iter()
+            .find(|(k, _)| k == key)
+            .map(|(_, v)| v.as_str())
+    }
+
+    /// An environment on the world of the map, keeping its
+    /// header as metadata.
+    pub fn into_environment(self) -> Environment {
+        Environment::from_grid(self.grid, self.start).with_metadata(self.metadata)
+    }
+}
+
+impl Display for Map {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        for (key, value) in &self.metadata {
+            f.write_fmt(format_args!("{}: {}\n", key, value))?;
+        }
+        if !self.metadata.is_empty() {
+            f.write_char('\n')?;
+        }
+
+        for i in 0..self.grid.nrows() {
+            let row = (0..self.grid.ncols())
+                .map(|j| match self.start == Pos::new(i, j) {
+                    true => START_GLYPH.to_string(),
+                    false => self.grid[i][j].get_kind().to_string(),
+                })
+                .collect::<Vec<_>>()
+                .join(" ");
+            f.write_str(&row)?;
+            f.write_char('\n')?;
+        }
+        Ok(())
+    }
+}
+
+/// Saves the world of `env`, as it was laid out, as a map file at
+/// `path`.
+pub fn save(env: &Environment, path: impl AsRef<Path>) -> Result<(), Error> {
+    std::fs::write(path, Map::from_env(env).to_string()).map_err(MapError::Io)?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const CLASSIC: &str = "\
+name: Classic
+
+_ _ _ P
+W G P _
+_ _ _ _
+A _ P _
+";
+
+    fn parse_error(text: &str) -> MapError {
+        match Map::parse(text) {
+            Err(Error::Map(err)) => err,
+            other => panic!("expected a map error, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn parses_header_grid_and_start() {
+        let map = Map::parse(CLASSIC).unwrap();
+        assert_eq!(map.get("name"), Some("Classic"));
+        assert_eq!(map.get("seed"), None);
+        assert_eq!(map.start, Pos::new(3, 0));
+        assert_eq!((map.grid.nrows(), map.grid.ncols()), (4, 4));
+        assert_eq!(*map.grid[1][0].get_kind(), RoomKind::Wumpus);
+        assert_eq!(*map.grid[1][1].get_kind(), RoomKind::Gold);
+        assert_eq!(*map.grid[3][2].get_kind(), RoomKind::Pit);
+    }
+
+    #[test]
+    fn skips_comments_and_blank_lines() {
+        let map = Map::parse("# a comment\n\nW G\n\n# another\nA _\n").unwrap();
+        assert_eq!(map.start, Pos::new(1, 0));
+        assert_eq!(map.to_string(), "W G\nA _\n");
+    }
+
+    #[test]
+    fn round_trips_through_display() {
+        let map = Map::parse(CLASSIC).unwrap();
+        assert_eq!(map.to_string(), CLASSIC);
+    }
+
+    #[test]
+    fn round_trips_through_an_environment() {
+        let env = Map::parse(CLASSIC).unwrap().into_environment();
+        assert_eq!(
+            env.metadata(),
+            &[("name".to_string(), "Classic".to_string())]
+        );
+        assert_eq!(Map::from_env(&env).to_string(), CLASSIC);
+    }
+
+    #[test]
+    fn rejects_unknown_glyphs() {
+        assert!(matches!(
+            parse_error("W G\nA X\n"),
+            MapError::Parse {
+                line: 2,
+                col: 3,
+                kind: ParseErrorKind::UnknownGlyph('X'),
+            }
+        ));
+    }
+
+    #[test]
+    fn rejects_ragged_rows() {
+        assert!(matches!(
+            parse_error("W G _\nA _\n"),
+            MapError::Parse {
+                line: 2,
+                kind: ParseErrorKind::RaggedRow {
+                    expected: 3,
+                    found: 2,
+                },
+                ..
+            }
+        ));
+    }
+
+    #[test]
+    fn rejects_duplicate_starts() {
+        assert!(matches!(
+            parse_error("W G\nA A\n"),
+            MapError::Parse {
+                line: 2,
+                col: 3,
+                kind: ParseErrorKind::DuplicateStart,
+            }
+        ));
+    }
+
+    #[test]
+    fn rejects_malformed_headers() {
+        assert!(matches!(
+            parse_error("world name: Classic\nW G\nA _\n"),
+            MapError::Parse {
+                line: 1,
+                col: 1,
+                kind: ParseErrorKind::MalformedHeader,
+            }
+        ));
+    }
+
+    #[test]
+    fn rejects_maps_without_a_grid() {
+        assert!(matches!(parse_error("name: Empty\n"), MapError::EmptyGrid));
+    }
+
+    #[test]
+    fn rejects_maps_without_a_start() {
+        assert!(matches!(parse_error("W G\n_ _\n"), MapError::MissingStart));
+    }
+
+    #[test]
+    fn rejects_worlds_breaking_the_rules() {
+        let MapError::Invalid(violations) = parse_error("_ G\nA _\n") else {
+            panic!("expected the world to be invalid");
+        };
+        assert_eq!(violations, vec![Violation::NoWumpus]);
+    }
+
+    #[test]
+    fn reports_unreadable_files() {
+        assert!(matches!(
+            Map::load("/nonexistent/world.map"),
+            Err(Error::Map(MapError::Io(_)))
+        ));
+    }
+}
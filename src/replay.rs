@@ -0,0 +1,640 @@
+use std::{
+    fmt::{Display, Write},
+    path::Path,
+};
+
+use crate::{
+    agent::{Action, Direction},
+    env::{ActionResult, Environment, Item, Ruleset, Sense, Snapshot},
+    error::Error,
+    map::Map,
+    scoring::{GoldReward, ScoringRules},
+};
+
+/// Line starting the world section of a replay
+const WORLD_SECTION: &str = "world:";
+/// Line starting the steps section of a replay
+const STEPS_SECTION: &str = "steps:";
+/// Separator between the fields of a step line
+const FIELD_SEPARATOR: char = '|';
+
+/// A recorded episode: the world it was played in, the rules it
+/// was played by, and every action taken along with its result
+/// and the score right after it.
+///
+/// ## Format
+///
+/// A header of `key: value` lines for the ruleset and the
+/// scoring rules, the world as a map after a `world:` line, and
+/// the steps after a `steps:` line, one per line as `action |
+/// result | score`. Lines starting with `#` are comments.
+///
+/// ```text
+/// ruleset: Absolute
+/// move_penalty: 1
+/// ...
+///
+/// world:
+/// seed: 42
+///
+/// _ _ _ P
+/// W G P _
+/// _ _ _ _
+/// A _ P _
+///
+/// steps:
+/// mv e | ok | -1
+/// mv n | game-over | -1002
+/// ```
+#[derive(Clone, Debug)]
+pub struct Replay {
+    map: Map,
+    ruleset: Ruleset,
+    scoring: ScoringRules,
+    steps: Vec<ReplayStep>,
+}
+
+/// An action taken in a recorded episode.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ReplayStep {
+    action: Action,
+    result: ActionResult,
+    score: isize,
+}
+
+impl ReplayStep {
+    /// The action taken.
+    pub fn action(&self) -> &Action {
+        &self.action
+    }
+
+    /// What taking the action led to.
+    pub fn result(&self) -> &ActionResult {
+        &self.result
+    }
+
+    /// The score right after the action.
+    pub fn score(&self) -> isize {
+        self.score
+    }
+}
+
+/// Errors raised while loading or re-executing a replay.
+#[derive(Debug)]
+pub enum ReplayError {
+    /// The replay file couldn't be read or written
+    Io(std::io::Error),
+    /// A malformed line, at the given 1-based line, along with
+    /// what was expected there
+    Parse {
+        /// The 1-based line
+        line: usize,
+        /// What was expected on it
+        expected: &'static str,
+    },
+    /// The replay has no world section
+    MissingWorld,
+    /// The given step led to another result than recorded
+    ResultMismatch {
+        /// The 1-based step
+        step: usize,
+        /// The recorded result
+        expected: ActionResult,
+        /// The result re-executing the step led to
+        found: ActionResult,
+    },
+    /// The given step led to another score than recorded
+    ScoreMismatch {
+        /// The 1-based step
+        step: usize,
+        /// The recorded score
+        expected: isize,
+        /// The score re-executing the step led to
+        found: isize,
+    },
+}
+
+impl Display for ReplayError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ReplayError::Io(err) => err.fmt(f),
+            ReplayError::Parse { line, expected } => {
+                f.write_fmt(format_args!("line {}: expected {}", line, expected))
+            }
+            ReplayError::MissingWorld => f.write_str("replay has no world"),
+            ReplayError::ResultMismatch {
+                step,
+                expected,
+                found,
+            } => f.write_fmt(format_args!(
+                "step {} led to {:?} instead of {:?}",
+                step, found, expected
+            )),
+            ReplayError::ScoreMismatch {
+                step,
+                expected,
+                found,
+            } => f.write_fmt(format_args!(
+                "step {} led to a score of {} instead of {}",
+                step, found, expected
+            )),
+        }
+    }
+}
+
+impl std::error::Error for ReplayError {}
+
+impl Replay {
+    /// Starts recording an episode of `env`, which must not have
+    /// started yet.
+    pub fn new(env: &Environment) -> Self {
+        Self {
+            map: Map::from_env(env),
+            ruleset: env.ruleset(),
+            scoring: env.scoring().clone(),
+            steps: Vec::new(),
+        }
+    }
+
+    /// Records `action`, which led to `result` and `score`.
+    pub fn record(&mut self, action: &Action, result: &ActionResult, score: isize) {
+        self.steps.push(ReplayStep {
+            action: action.clone(),
+            result: result.clone(),
+            score,
+        });
+    }
+
+    /// Drops the steps recorded after the first `len` ones, as
+    /// when undoing them.
+    pub fn truncate(&mut self, len: usize) {
+        self.steps.truncate(len);
+    }
+
+    /// The recorded steps, in order.
+    pub fn steps(&self) -> &[ReplayStep] {
+        &self.steps
+    }
+
+    /// Builds a fresh environment for the recorded episode.
+    pub fn environment(&self) -> Environment {
+        self.map
+            .clone()
+            .into_environment()
+            .with_ruleset(self.ruleset)
+            .with_scoring(self.scoring.clone())
+    }
+
+    /// Re-executes the recorded actions against a fresh
+    /// environment, checking each result and score against the
+    /// recorded ones. Returns the state of the episode before the
+    /// first step and after each one.
+    pub fn verify(&self) -> Result<Vec<Snapshot>, Error> {
+        let mut env = self.environment();
+        let mut snapshots = vec![env.snapshot()];
+        for (i, step) in self.steps.iter().enumerate() {
+            let result = env.step_with_reward(&step.action)?.info;
+            if result != step.result {
+                return Err(Error::Replay(ReplayError::ResultMismatch {
+                    step: i + 1,
+                    expected: step.result.clone(),
+                    found: result,
+                }));
+            }
+            if env.score() != step.score {
+                return Err(Error::Replay(ReplayError::ScoreMismatch {
+                    step: i + 1,
+                    expected: step.score,
+                    found: env.score(),
+                }));
+            }
+            snapshots.push(env.snapshot());
+        }
+
+        Ok(snapshots)
+    }
+
+    /// Reads and parses the replay file at `path`.
+    pub fn load(path: impl AsRef<Path>) -> Result<Self, Error> {
+        let text = std::fs::read_to_string(path).map_err(ReplayError::Io)?;
+        Self::parse(&text)
+    }
+
+    /// Saves the replay as a file at `path`.
+    pub fn save(&self, path: impl AsRef<Path>) -> Result<(), Error> {
+        std::fs::write(path, self.to_string()).map_err(ReplayError::Io)?;
+        Ok(())
+    }
+
+    /// Parses a replay from the text of a replay file.
+    pub fn parse(text: &str) -> Result<Self, Error> {
+        let lines: Vec<&str> = text.lines().collect();
+        let find = |section: &str| lines.iter().position(|line| line.trim() == section);
+        let Some(world_start) = find(WORLD_SECTION) else {
+            return Err(ReplayError::MissingWorld.into());
+        };
+        let steps_start = find(STEPS_SECTION).unwrap_or(lines.len());
+
+        let mut ruleset = Ruleset::default();
+        let mut scoring = ScoringRules::default();
+        for (index, line) in lines[..world_start].iter().enumerate() {
+            let trimmed = line.trim();
+            if trimmed.is_empty() || trimmed.starts_with('#') {
+                continue;
+            }
+            let parse_error = |expected| ReplayError::Parse {
+                line: index + 1,
+                expected,
+            };
+            let (key, value) = trimmed
+                .split_once(':')
+                .ok_or(parse_error("a `key: value` line"))?;
+            let value = value.trim();
+            let number = || value.parse().map_err(|_| parse_error("a number"));
+            let limit = || parse_limit(value).ok_or(parse_error("a number or `none`"));
+            match key.trim() {
+                "ruleset" => {
+                    ruleset = match value {
+                        "Absolute" => Ruleset::Absolute,
+                        "Aima" => Ruleset::Aima,
+                        _ => return Err(parse_error("a ruleset").into()),
+                    }
+                }
+                "move_penalty" => scoring.move_penalty = number()?,
+                "action_penalty" => scoring.action_penalty = number()?,
+                "arrow_penalty" => scoring.arrow_penalty = number()?,
+                "pit_penalty" => scoring.pit_penalty = number()?,
+                "wumpus_penalty" => scoring.wumpus_penalty = number()?,
+                "gold_reward" => scoring.gold_reward = number()?,
+                "gold_reward_on" => {
+                    scoring.gold_reward_on = match value {
+                        "OnClimb" => GoldReward::OnClimb,
+                        "OnGrab" => GoldReward::OnGrab,
+                        _ => return Err(parse_error("when the gold reward is granted").into()),
+                    }
+                }
+                "wumpus_kill_bonus" => scoring.wumpus_kill_bonus = number()?,
+                "step_limit" => scoring.step_limit = limit()?,
+                "repeat_limit" => scoring.repeat_limit = limit()?,
+                "step_limit_penalty" => scoring.step_limit_penalty = number()?,
+                "repeat_limit_penalty" => scoring.repeat_limit_penalty = number()?,
+                _ => return Err(parse_error("a ruleset or scoring rule").into()),
+            }
+        }
+
+        let map = Map::parse(&lines[world_start + 1..steps_start].join("\n"))?;
+
+        let mut steps = Vec::new();
+        for (index, line) in lines.iter().enumerate().skip(steps_start + 1) {
+            let trimmed = line.trim();
+            if trimmed.is_empty() || trimmed.starts_with('#') {
+                continue;
+            }
+            let parse_error = |expected| ReplayError::Parse {
+                line: index + 1,
+                expected,
+            };
+            let fields: Vec<&str> = trimmed.split(FIELD_SEPARATOR).map(str::trim).collect();
+            let [action, result, score] = fields[..] else {
+                return Err(parse_error("`action | result | score`").into());
+            };
+            steps.push(ReplayStep {
+                action: parse_action(action).ok_or(parse_error("an action"))?,
+                result: parse_result(result).ok_or(parse_error("an action result"))?,
+                score: score.parse().map_err(|_| parse_error("a score"))?,
+            });
+        }
+
+        Ok(Self {
+            map,
+            ruleset,
+            scoring,
+            steps,
+        })
+    }
+}
+
+impl Display for Replay {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let rules = &self.scoring;
+        f.write_fmt(format_args!("ruleset: {:?}\n", self.ruleset))?;
+        f.write_fmt(format_args!("move_penalty: {}\n", rules.move_penalty))?;
+        f.write_fmt(format_args!("action_penalty: {}\n", rules.action_penalty))?;
+        f.write_fmt(format_args!("arrow_penalty: {}\n", rules.arrow_penalty))?;
+        f.write_fmt(format_args!("pit_penalty: {}\n", rules.pit_penalty))?;
+        f.write_fmt(format_args!("wumpus_penalty: {}\n", rules.wumpus_penalty))?;
+        f.write_fmt(format_args!("gold_reward: {}\n", rules.gold_reward))?;
+        f.write_fmt(format_args!("gold_reward_on: {:?}\n", rules.gold_reward_on))?;
+        f.write_fmt(format_args!(
+            "wumpus_kill_bonus: {}\n",
+            rules.wumpus_kill_bonus
+        ))?;
+        f.write_fmt(format_args!(
+            "step_limit: {}\n",
+            format_limit(rules.step_limit)
+        ))?;
+        f.write_fmt(format_args!(
+            "repeat_limit: {}\n",
+            format_limit(rules.repeat_limit)
+        ))?;
+        f.write_fmt(format_args!(
+            "step_limit_penalty: {}\n",
+            rules.step_limit_penalty
+        ))?;
+        f.write_fmt(format_args!(
+            "repeat_limit_penalty: {}\n",
+            rules.repeat_limit_penalty
+        ))?;
+
+        f.write_char('\n')?;
+        f.write_str(WORLD_SECTION)?;
+        f.write_char('\n')?;
+        f.write_fmt(format_args!("{}", self.map))?;
+
+        f.write_char('\n')?;
+        f.write_str(STEPS_SECTION)?;
+        f.write_char('\n')?;
+        for step in &self.steps {
+            f.write_fmt(format_args!(
+                "{} {} {} {} {}\n",
+                format_action(&step.action),
+                FIELD_SEPARATOR,
+                format_result(&step.result),
+                FIELD_SEPARATOR,
+                step.score
+            ))?;
+        }
+        Ok(())
+    }
+}
+
+/// Writes an optional limit as a number, or `none` for no limit.
+fn format_limit(limit: Option<usize>) -> String {
+    match limit {
+        Some(limit) => limit.to_string(),
+        None => "none".to_string(),
+    }
+}
+
+/// Reads a limit written by `format_limit`, returning `None` when
+/// it is neither a number nor `none`.
+fn parse_limit(text: &str) -> Option<Option<usize>> {
+    match text {
+        "none" => Some(None),
+        _ => text.parse().ok().map(Some),
+    }
+}
+
+pub(crate) fn format_direction(direction: &Direction) -> &'static str {
+    match direction {
+        Direction::North => "n",
+        Direction::South => "s",
+        Direction::East => "e",
+        Direction::West => "w",
+    }
+}
+
+fn parse_direction(text: &str) -> Option<Direction> {
+    match text {
+        "n" => Some(Direction::North),
+        "s" => Some(Direction::South),
+        "e" => Some(Direction::East),
+        "w" => Some(Direction::West),
+        _ => None,
+    }
+}
+
+/// Writes `action` as the player command taking it.
+pub(crate) fn format_action(action: &Action) -> String {
+    match action {
+        Action::Move(direction) => format!("mv {}", format_direction(direction)),
+        Action::Shoot(direction) => format!("sh {}", format_direction(direction)),
+        Action::Climb => "cl".to_string(),
+        Action::Grab => "gr".to_string(),
+        Action::Forward => "fw".to_string(),
+        Action::TurnLeft => "tl".to_string(),
+        Action::TurnRight => "tr".to_string(),
+        Action::ShootForward => "sh".to_string(),
+    }
+}
+
+pub(crate) fn parse_action(text: &str) -> Option<Action> {
+    match text.split_once(' ') {
+        Some(("mv", direction)) => parse_direction(direction.trim()).map(Action::Move),
+        Some(("sh", direction)) => parse_direction(direction.trim()).map(Action::Shoot),
+        Some(_) => None,
+        None => match text {
+            "cl" => Some(Action::Climb),
+            "gr" => Some(Action::Grab),
+            "fw" => Some(Action::Forward),
+            "tl" => Some(Action::TurnLeft),
+            "tr" => Some(Action::TurnRight),
+            "sh" => Some(Action::ShootForward),
+            _ => None,
+        },
+    }
+}
+
+pub(crate) fn format_result(result: &ActionResult) -> String {
+    match result {
+        ActionResult::Ok => "ok".to_string(),
+        ActionResult::GameOver => "game-over".to_string(),
+        ActionResult::Sense(Sense::Stench) => "stench".to_string(),
+        ActionResult::Sense(Sense::Breeze) => "breeze".to_string(),
+        ActionResult::Sense(Sense::Scream) => "scream".to_string(),
+        ActionResult::Sense(Sense::Glitter) => "glitter".to_string(),
+        ActionResult::Sense(Sense::Bump(direction)) => {
+            format!("bump {}", format_direction(direction))
+        }
+        ActionResult::Sense(Sense::Ceil) => "ceil".to_string(),
+        ActionResult::Grabbed(Item::Gold) => "grabbed gold".to_string(),
+        ActionResult::OutOfArrows => "out-of-arrows".to_string(),
+    }
+}
+
+fn parse_result(text: &str) -> Option<ActionResult> {
+    match text.split_once(' ') {
+        Some(("bump", direction)) => {
+            parse_direction(direction.trim()).map(|d| ActionResult::Sense(Sense::Bump(d)))
+        }
+        Some(("grabbed", "gold")) => Some(ActionResult::Grabbed(Item::Gold)),
+        Some(_) => None,
+        None => match text {
+            "ok" => Some(ActionResult::Ok),
+            "game-over" => Some(ActionResult::GameOver),
+            "stench" => Some(ActionResult::Sense(Sense::Stench)),
+            "breeze" => Some(ActionResult::Sense(Sense::Breeze)),
+            "scream" => Some(ActionResult::Sense(Sense::Scream)),
+            "glitter" => Some(ActionResult::Sense(Sense::Glitter)),
+            "ceil" => Some(ActionResult::Sense(Sense::Ceil)),
+            "out-of-arrows" => Some(ActionResult::OutOfArrows),
+            _ => None,
+        },
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{env::GridType, map::MapError};
+
+    /// Plays `actions` on the classic world, recording them.
+    fn record(actions: &[Action]) -> Replay {
+        let mut env = Environment::new(GridType::Classic);
+        let mut replay = Replay::new(&env);
+        for action in actions {
+            let step = env.step_with_reward(action).unwrap();
+            replay.record(action, &step.info, env.score());
+        }
+        replay
+    }
+
+    fn sample() -> Replay {
+        record(&[
+            Action::Move(Direction::East),
+            Action::Move(Direction::North),
+            Action::Move(Direction::North),
+            Action::Grab,
+            Action::Shoot(Direction::West),
+            Action::Move(Direction::South),
+            Action::Move(Direction::South),
+            Action::Move(Direction::West),
+            Action::Climb,
+        ])
+    }
+
+    fn parse_error(text: &str) -> ReplayError {
+        match Replay::parse(text) {
+            Err(Error::Replay(err)) => err,
+            other => panic!("expected a replay error, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn round_trips_through_display() {
+        let replay = sample();
+        let text = replay.to_string();
+        let parsed = Replay::parse(&text).unwrap();
+        assert_eq!(parsed.steps(), replay.steps());
+        assert_eq!(parsed.ruleset, replay.ruleset);
+        assert_eq!(parsed.scoring, replay.scoring);
+        assert_eq!(parsed.to_string(), text);
+    }
+
+    #[test]
+    fn round_trips_limits() {
+        let mut replay = sample();
+        replay.scoring.step_limit = Some(50);
+        replay.scoring.repeat_limit = None;
+        let parsed = Replay::parse(&replay.to_string()).unwrap();
+        assert_eq!(parsed.scoring.step_limit, Some(50));
+        assert_eq!(parsed.scoring.repeat_limit, None);
+    }
+
+    #[test]
+    fn verifies_a_faithful_replay() {
+        let replay = Replay::parse(&sample().to_string()).unwrap();
+        let snapshots = replay.verify().unwrap();
+        assert_eq!(snapshots.len(), replay.steps().len() + 1);
+    }
+
+    #[test]
+    fn catches_a_tampered_score() {
+        let text = sample()
+            .to_string()
+            .replace("gr | grabbed gold | -3", "gr | grabbed gold | 997");
+        let replay = Replay::parse(&text).unwrap();
+        assert!(matches!(
+            replay.verify(),
+            Err(Error::Replay(ReplayError::ScoreMismatch {
+                step: 4,
+                expected: 997,
+                found: -3,
+            }))
+        ));
+    }
+
+    #[test]
+    fn catches_a_tampered_result() {
+        let text = sample().to_string().replace("sh w | scream", "sh w | ok");
+        let replay = Replay::parse(&text).unwrap();
+        assert!(matches!(
+            replay.verify(),
+            Err(Error::Replay(ReplayError::ResultMismatch {
+                step: 5,
+                expected: ActionResult::Ok,
+                found: ActionResult::Sense(Sense::Scream),
+            }))
+        ));
+    }
+
+    #[test]
+    fn rejects_replays_without_a_world() {
+        assert!(matches!(
+            parse_error("ruleset: Absolute\n"),
+            ReplayError::MissingWorld
+        ));
+    }
+
+    #[test]
+    fn rejects_malformed_lines() {
+        let cases = [
+            (
+                "ruleset Absolute\nworld:\nA G W\n",
+                1,
+                "a `key: value` line",
+            ),
+            ("ruleset: Textbook\nworld:\nA G W\n", 1, "a ruleset"),
+            ("move_penalty: one\nworld:\nA G W\n", 1, "a number"),
+            (
+                "step_limit: never\nworld:\nA G W\n",
+                1,
+                "a number or `none`",
+            ),
+            (
+                "gold_reward_on: OnExit\nworld:\nA G W\n",
+                1,
+                "when the gold reward is granted",
+            ),
+            ("wind: 3\nworld:\nA G W\n", 1, "a ruleset or scoring rule"),
+            (
+                "world:\nA G W\nsteps:\nmv e | ok\n",
+                4,
+                "`action | result | score`",
+            ),
+            ("world:\nA G W\nsteps:\njump | ok | 0\n", 4, "an action"),
+            (
+                "world:\nA G W\nsteps:\nmv e | bounce | 0\n",
+                4,
+                "an action result",
+            ),
+            ("world:\nA G W\nsteps:\nmv e | ok | lots\n", 4, "a score"),
+        ];
+        for (text, line, expected) in cases {
+            assert!(
+                matches!(
+                    parse_error(text),
+                    ReplayError::Parse { line: l, expected: e } if l == line && e == expected
+                ),
+                "{}",
+                text
+            );
+        }
+    }
+
+    #[test]
+    fn reports_errors_of_the_world() {
+        assert!(matches!(
+            Replay::parse("world:\nA G\n"),
+            Err(Error::Map(MapError::Invalid(_)))
+        ));
+    }
+
+    #[test]
+    fn reports_unreadable_files() {
+        assert!(matches!(
+            Replay::load("/nonexistent/episode.replay"),
+            Err(Error::Replay(ReplayError::Io(_)))
+        ));
+    }
+}
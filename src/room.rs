@@ -3,13 +3,18 @@ use std::{
     fmt::{Display, Write},
 };
 
-use crate::env::Sense;
+use crate::{env::Sense, error::Error};
 
-#[derive(PartialEq)]
+/// What a room holds.
+#[derive(PartialEq, Clone, Debug)]
 pub enum RoomKind {
+    /// Nothing
     Void,
+    /// A pit the agent falls into
     Pit,
+    /// The Wumpus, alive
     Wumpus,
+    /// A piece of gold
     Gold,
 }
 
@@ -24,6 +29,8 @@ impl Display for RoomKind {
     }
 }
 
+/// A room of the grid, along with what can be sensed in it.
+#[derive(Clone, Debug)]
 pub struct Room {
     kind: RoomKind,
     senses: HashSet<Sense>,
@@ -31,6 +38,7 @@ pub struct Room {
 }
 
 impl Room {
+    /// An empty, unvisited room.
     pub fn new() -> Room {
         Room {
             kind: RoomKind::Void,
@@ -39,39 +47,56 @@ impl Room {
         }
     }
 
+    /// Changes what the room holds.
     pub fn set_kind(&mut self, kind: RoomKind) {
         self.kind = kind;
     }
 
+    /// What the room holds.
     pub fn get_kind(&self) -> &RoomKind {
         &self.kind
     }
 
+    /// Whether the agent has been in the room.
     pub fn is_visited(&self) -> bool {
         self.visited
     }
 
+    /// Marks the room as visited or not.
     pub fn set_visited(&mut self, visited: bool) {
         self.visited = visited;
     }
 
+    /// What can be sensed in the room.
     pub fn senses(&self) -> &HashSet<Sense> {
         &self.senses
     }
 
+    /// Mutable access to what can be sensed in the room, bypassing
+    /// the checks of `add_sense`.
     pub fn mut_senses(&mut self) -> &mut HashSet<Sense> {
         &mut self.senses
     }
 
-    pub fn add_sense(&mut self, sense: Sense) {
+    /// Adds `sense` to the room, which can only be a breeze, a
+    /// stench or a glitter.
+    pub fn add_sense(&mut self, sense: Sense) -> Result<(), Error> {
         if sense != Sense::Breeze && sense != Sense::Stench && sense != Sense::Glitter {
-            panic!("{:?} is not a room sense!", sense);
+            return Err(Error::UnexpectedSense(sense));
         }
 
         self.senses.insert(sense);
+        Ok(())
     }
 
+    /// Whether `sense` can be sensed in the room.
     pub fn has_sense(&self, sense: Sense) -> bool {
         self.senses.contains(&sense)
     }
 }
+
+impl Default for Room {
+    fn default() -> Self {
+        Self::new()
+    }
+}
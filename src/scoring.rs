@@ -0,0 +1,167 @@
+use crate::env::Ruleset;
+
+/// Steps allowed per room of the world by `default_step_limit`.
+pub const STEPS_PER_ROOM: usize = 25;
+
+/// The step limit of runs nobody is watching, so an agent going in
+/// circles can't keep them going forever: `STEPS_PER_ROOM` steps
+/// for each room of a `nrows` by `ncols` world.
+pub fn default_step_limit(nrows: usize, ncols: usize) -> usize {
+    nrows * ncols * STEPS_PER_ROOM
+}
+
+/// When the gold reward is granted.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum GoldReward {
+    /// When climbing out of the cave carrying the gold, as in the
+    /// textbook
+    #[default]
+    OnClimb,
+    /// As soon as the gold is grabbed, whether the agent makes it out
+    /// of the cave or not
+    OnGrab,
+}
+
+/// The penalties and rewards an `Environment` scores with.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ScoringRules {
+    /// Charged on each move into another room
+    pub move_penalty: isize,
+    /// Charged on every action, whatever it is
+    pub action_penalty: isize,
+    /// Charged on every shot
+    pub arrow_penalty: isize,
+    /// Charged when falling into a pit
+    pub pit_penalty: isize,
+    /// Charged when walking into the Wumpus
+    pub wumpus_penalty: isize,
+    /// Granted for each gold piece
+    pub gold_reward: isize,
+    /// When the gold reward is granted
+    pub gold_reward_on: GoldReward,
+    /// Granted when killing the Wumpus
+    pub wumpus_kill_bonus: isize,
+    /// Number of steps after which the episode is cut short, if any
+    pub step_limit: Option<usize>,
+    /// Number of times the agent may come back to the same position,
+    /// orientation, arrows, gold and count of visited rooms before
+    /// the episode is cut short, if any
+    pub repeat_limit: Option<usize>,
+    /// Charged when the episode is cut short by the step limit
+    pub step_limit_penalty: isize,
+    /// Charged when the episode is cut short by the repeat limit
+    pub repeat_limit_penalty: isize,
+}
+
+impl ScoringRules {
+    /// The game's own rules: moving costs a point, and the gold
+    /// reward is granted on climbing out with the gold. Before
+    /// that reward was tied to escaping, it was granted on
+    /// grabbing the gold, which `GoldReward::OnGrab` brings back.
+    pub fn classic() -> Self {
+        Self {
+            move_penalty: 1,
+            action_penalty: 0,
+            arrow_penalty: 10,
+            pit_penalty: 1000,
+            wumpus_penalty: 1000,
+            gold_reward: 1000,
+            gold_reward_on: GoldReward::OnClimb,
+            wumpus_kill_bonus: 0,
+            step_limit: None,
+            repeat_limit: None,
+            step_limit_penalty: 0,
+            repeat_limit_penalty: 0,
+        }
+    }
+
+    /// The textbook rules, where every action costs a point,
+    /// moving or not.
+    pub fn aima() -> Self {
+        Self {
+            move_penalty: 0,
+            action_penalty: 1,
+            ..Self::classic()
+        }
+    }
+
+    /// The preset matching `ruleset`.
+    pub fn preset(ruleset: Ruleset) -> Self {
+        match ruleset {
+            Ruleset::Absolute => Self::classic(),
+            Ruleset::Aima => Self::aima(),
+        }
+    }
+}
+
+impl Default for ScoringRules {
+    fn default() -> Self {
+        Self::classic()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        agent::{Action, Direction},
+        map::Map,
+    };
+
+    #[test]
+    fn presets_keep_their_values() {
+        let classic = ScoringRules::classic();
+        assert_eq!(
+            classic,
+            ScoringRules {
+                move_penalty: 1,
+                action_penalty: 0,
+                arrow_penalty: 10,
+                pit_penalty: 1000,
+                wumpus_penalty: 1000,
+                gold_reward: 1000,
+                gold_reward_on: GoldReward::OnClimb,
+                wumpus_kill_bonus: 0,
+                step_limit: None,
+                repeat_limit: None,
+                step_limit_penalty: 0,
+                repeat_limit_penalty: 0,
+            }
+        );
+        assert_eq!(
+            ScoringRules::aima(),
+            ScoringRules {
+                move_penalty: 0,
+                action_penalty: 1,
+                ..classic.clone()
+            }
+        );
+        assert_eq!(ScoringRules::default(), classic);
+        assert_eq!(ScoringRules::preset(Ruleset::Absolute), classic);
+        assert_eq!(ScoringRules::preset(Ruleset::Aima), ScoringRules::aima());
+    }
+
+    #[test]
+    fn allows_steps_by_the_room() {
+        assert_eq!(default_step_limit(4, 4), 400);
+        assert_eq!(default_step_limit(3, 6), 450);
+    }
+
+    #[test]
+    fn grants_the_kill_bonus_on_the_scream() {
+        let mut env = Map::parse("A _ W\n_ _ G\n")
+            .unwrap()
+            .into_environment()
+            .with_scoring(ScoringRules {
+                wumpus_kill_bonus: 500,
+                ..ScoringRules::classic()
+            });
+        env.step(&Action::Shoot(Direction::East)).unwrap();
+        assert_eq!(env.score(), 490);
+
+        // No bonus for missing
+        env.initialize();
+        env.step(&Action::Shoot(Direction::South)).unwrap();
+        assert_eq!(env.score(), -10);
+    }
+}
@@ -1,6 +1,18 @@
-use std::io::{stdout, Write};
+use std::{
+    collections::HashMap,
+    io::{Write, stdout},
+};
 
-use crate::{agent::Direction, env::Environment};
+use wumpus_world::{
+    agent::{Belief, Decision, Direction, Percept},
+    arena::ArenaReport,
+    bench::{BenchReport, outcome_label},
+    env::{Environment, Outcome, Ruleset, Truncation},
+    grid::Pos,
+    replay::ReplayStep,
+    scoring::{GoldReward, ScoringRules},
+    world::{PitPolicy, Solvability, StartPolicy, WorldConfig},
+};
 
 fn flush() {
     stdout().flush().unwrap();
@@ -18,6 +30,155 @@ pub fn print_prompt() {
     flush();
 }
 
+/// Prompts for a number, falling back to `default` on an
+/// empty input.
+pub fn read_number(label: &str, default: usize) -> usize {
+    loop {
+        print!("{} [{}]: ", label, default);
+        flush();
+        let input = read_command();
+        if input.is_empty() {
+            return default;
+        }
+        match input.parse() {
+            Ok(number) => return number,
+            Err(_) => invalid_input(),
+        }
+    }
+}
+
+/// Prompts for a world seed, returning `None` on an empty
+/// input so that a fresh one is picked.
+pub fn read_seed() -> Option<u64> {
+    loop {
+        print!("Seed [random]: ");
+        flush();
+        let input = read_command();
+        if input.is_empty() {
+            return None;
+        }
+        match input.parse() {
+            Ok(seed) => return Some(seed),
+            Err(_) => invalid_input(),
+        }
+    }
+}
+
+/// Prompts for the parameters of a random world, which always
+/// has a single Wumpus. Pits are given either as a count or as a
+/// percentage, and the start position as `row,col`.
+pub fn read_world_config() -> WorldConfig {
+    let defaults = WorldConfig::default();
+    let rows = read_number("Rows", *defaults.rows.start());
+    let cols = read_number("Columns", *defaults.cols.start());
+    let pits = loop {
+        let input = read_field("Pits, count or percentage", "20%");
+        let parsed = match input.strip_suffix('%') {
+            Some(percent) => percent
+                .parse::<f64>()
+                .ok()
+                .map(|percent| PitPolicy::Probability(percent / 100.0)),
+            None => input.parse().ok().map(PitPolicy::Count),
+        };
+        match parsed {
+            Some(pits) => break pits,
+            None => invalid_input(),
+        }
+    };
+    let gold = read_number("Gold pieces", defaults.gold);
+    let start = loop {
+        let input = read_field("Start row,col", "random");
+        if input == "random" {
+            break StartPolicy::Random;
+        }
+        match input
+            .split_once(',')
+            .map(|(r, c)| (r.trim().parse(), c.trim().parse()))
+        {
+            Some((Ok(row), Ok(col))) => break StartPolicy::Fixed(Pos::new(row, col)),
+            _ => invalid_input(),
+        }
+    };
+    let solvability = loop {
+        match read_field("Solvability (any, reachable, provable)", "any").as_str() {
+            "any" => break Solvability::Any,
+            "reachable" => break Solvability::Reachable,
+            "provable" => break Solvability::Provable,
+            _ => invalid_input(),
+        }
+    };
+
+    WorldConfig {
+        pits,
+        gold,
+        start,
+        solvability,
+        ..WorldConfig::with_size(rows, cols)
+    }
+}
+
+/// Prompts for the scoring rules, starting from the preset of
+/// `ruleset`.
+pub fn read_scoring(ruleset: Ruleset) -> ScoringRules {
+    let mut rules = ScoringRules::preset(ruleset);
+    if yes_no("Reward gold as soon as it is grabbed?") {
+        rules.gold_reward_on = GoldReward::OnGrab;
+    }
+    rules.step_limit = read_limit("Step limit");
+    if rules.step_limit.is_some() {
+        rules.step_limit_penalty = read_number("Penalty when out of steps", 0) as isize;
+    }
+    rules.repeat_limit = read_limit("Repeated state limit");
+    if rules.repeat_limit.is_some() {
+        rules.repeat_limit_penalty =
+            read_number("Penalty when going around in circles", 0) as isize;
+    }
+    rules
+}
+
+/// Prompts for an optional limit, returning `None` on an empty
+/// input for no limit.
+fn read_limit(label: &str) -> Option<usize> {
+    loop {
+        let input = read_field(label, "none");
+        if input == "none" {
+            return None;
+        }
+        match input.parse() {
+            Ok(limit) => return Some(limit),
+            Err(_) => invalid_input(),
+        }
+    }
+}
+
+pub fn read_path(label: &str) -> String {
+    print!("{}: ", label);
+    flush();
+    read_command()
+}
+
+/// Prompts for a file to write some output to, returning `None`
+/// on an empty input to not write any.
+pub fn read_output_path(label: &str) -> Option<String> {
+    let path = read_field(label, "none");
+    match path.as_str() {
+        "none" => None,
+        _ => Some(path),
+    }
+}
+
+/// Prompts for a free-form field, falling back to `default` on
+/// an empty input.
+fn read_field(label: &str, default: &str) -> String {
+    print!("{} [{}]: ", label, default);
+    flush();
+    let input = read_command();
+    match input.is_empty() {
+        true => default.to_string(),
+        false => input,
+    }
+}
+
 pub fn parse_direction(cmd: &str) -> Option<Direction> {
     match cmd.split_whitespace().nth(1) {
         Some(d) => match d {
@@ -31,6 +192,10 @@ pub fn parse_direction(cmd: &str) -> Option<Direction> {
     }
 }
 
+pub fn display_error(err: &dyn std::error::Error) {
+    println!("Error: {}", err);
+}
+
 pub fn invalid_input() {
     println!("Invalid input!");
 }
@@ -43,27 +208,100 @@ pub fn main_menu() {
     println!();
     println!("[p] Play");
     println!("[a] Run Agent");
+    println!("[r] Watch Replay");
+    println!("[n] Benchmark");
+    println!("[v] Arena");
     println!("[h] Help");
     println!();
     println!("[q] Quit");
     print_prompt();
 }
 
-pub fn play_help() {
+/// Prompts for the ruleset to play by, returning `None` to go
+/// back.
+pub fn read_ruleset() -> Option<Ruleset> {
+    loop {
+        println!("Choose ruleset: ");
+        println!();
+        println!("[c] Classic (absolute directions)");
+        println!("[a] AIMA (orientation and turns)");
+        println!();
+        println!("[b] Back");
+        print_prompt();
+        match read_command().as_str() {
+            "c" => return Some(Ruleset::Absolute),
+            "a" => return Some(Ruleset::Aima),
+            "b" => return None,
+            _ => {}
+        }
+    }
+}
+
+pub fn not_in_ruleset(ruleset: Ruleset) {
+    println!("Not available under the {:?} ruleset! See [?].", ruleset);
+}
+
+pub fn display_orientation(orientation: &Direction) {
+    println!("Facing {:?}", orientation);
+}
+
+pub fn display_percept(percept: &Percept) {
+    println!("Percept: {}", percept);
+}
+
+/// Lists the commands of player mode, with the actions of
+/// `ruleset` only.
+pub fn play_help(ruleset: Ruleset) {
     println!();
     println!("[g?] View grid");
-    println!("[s?] View score");
-    println!("[mv] Move [n,s,e,w]");
-    println!("[sh] Shoot arrow [n,s,e,w]");
+    println!("[s?] View score and inventory");
+    println!("[r?] View scoring rules");
+    match ruleset {
+        Ruleset::Absolute => {
+            println!("[mv] Move [n,s,e,w]");
+            println!("[sh] Shoot arrow [n,s,e,w]");
+        }
+        Ruleset::Aima => {
+            println!("[fw] Move forward");
+            println!("[tl] Turn left");
+            println!("[tr] Turn right");
+            println!("[sh] Shoot arrow forward");
+        }
+    }
     println!("[cl] Climb out");
     println!("[gr] Grab");
+    println!("[sv] Save map [path]");
+    println!("[sr] Save replay [path]");
+    println!("[u] Undo last action");
     println!();
     println!("[?] Help");
     println!("[b] Back to Main Menu");
 }
 
-pub fn game_over(score: isize) -> bool {
-    println!("Game Over! Score: {}", score);
+/// Prints the score and outcome of the episode that just ended.
+pub fn display_summary(env: &Environment) {
+    match env.seed() {
+        Some(seed) => println!(
+            "Game Over! Score: {} after {} steps (seed {})",
+            env.score(),
+            env.steps(),
+            seed
+        ),
+        None => println!(
+            "Game Over! Score: {} after {} steps",
+            env.score(),
+            env.steps()
+        ),
+    }
+    if let Some(outcome) = env.outcome() {
+        println!("{}", outcome);
+    }
+}
+
+/// Prints the episode summary, and asks whether to replay.
+/// Returns `true` when done playing.
+pub fn game_over(env: &Environment) -> bool {
+    display_summary(env);
     println!();
     loop {
         print!("Replay? [y,n]: ");
@@ -76,9 +314,9 @@ pub fn game_over(score: isize) -> bool {
     }
 }
 
-pub fn confirm() -> bool {
+pub fn yes_no(question: &str) -> bool {
     loop {
-        print!("Are you sure? [y,n]: ");
+        print!("{} [y,n]: ", question);
         flush();
         match read_command().as_str() {
             "y" => return true,
@@ -88,7 +326,73 @@ pub fn confirm() -> bool {
     }
 }
 
-pub fn general_help() {
+pub fn confirm() -> bool {
+    yes_no("Are you sure?")
+}
+
+pub fn replay_help() {
+    println!();
+    println!("[n] Next step");
+    println!("[p] Previous step");
+    println!();
+    println!("[?] Help");
+    println!("[b] Back to Main Menu");
+}
+
+pub fn display_replay_step(index: usize, step: &ReplayStep) {
+    println!(
+        "Step {}: {:?} -> {:?}, score {}",
+        index,
+        step.action(),
+        step.result(),
+        step.score()
+    );
+}
+
+pub fn display_decision(decision: &Decision) {
+    println!("Agent {}", decision);
+}
+
+/// Prints the penalties and rewards of `rules`.
+pub fn display_rules(rules: &ScoringRules) {
+    println!("Penalties & Rewards:");
+    if rules.move_penalty != 0 {
+        println!("  - Move penalty: {}", rules.move_penalty);
+    }
+    if rules.action_penalty != 0 {
+        println!(
+            "  - Action penalty: {} (on every action)",
+            rules.action_penalty
+        );
+    }
+    println!("  - Pit penalty: {}", rules.pit_penalty);
+    println!("  - Wumpus penalty: {}", rules.wumpus_penalty);
+    println!("  - Arrow penalty: {} (on every shot)", rules.arrow_penalty);
+    if rules.wumpus_kill_bonus != 0 {
+        println!("  - Wumpus kill bonus: {}", rules.wumpus_kill_bonus);
+    }
+    match rules.gold_reward_on {
+        GoldReward::OnClimb => println!(
+            "  - Gold reward: {} (once climbing out with it)",
+            rules.gold_reward
+        ),
+        GoldReward::OnGrab => println!("  - Gold reward: {} (once grabbed)", rules.gold_reward),
+    }
+    if let Some(limit) = rules.step_limit {
+        println!(
+            "  - Step limit: {} (penalty: {})",
+            limit, rules.step_limit_penalty
+        );
+    }
+    if let Some(limit) = rules.repeat_limit {
+        println!(
+            "  - Repeated state limit: {} (penalty: {})",
+            limit, rules.repeat_limit_penalty
+        );
+    }
+}
+
+pub fn general_help(rules: &ScoringRules) {
     println!("\n=?");
     println!("The Wumpus World is a cave exploration game where you navigate");
     println!("a grid to find gold while avoiding the Wumpus and pits.");
@@ -101,18 +405,18 @@ pub fn general_help() {
     println!("    positions are breezy.");
     println!("  - A position contains the Wumpus iff all neighboring");
     println!("    positions are stenchy.");
-    println!("  - You have only one arrow in your pocket.");
+    println!("  - You have only one arrow in your pocket. It flies");
+    println!("    straight until it hits the Wumpus or a wall, and");
+    println!("    the Wumpus' scream is heard all over the cave.");
     println!();
-    println!("Penalties & Rewards:");
-    println!("  - Move penalty: 1");
-    println!("  - Pit penalty: 1000");
-    println!("  - Wumpus penalty: 1000");
-    println!("  - Arrow penalty: 10");
-    println!("  - Gold reward: 1000");
+    display_rules(rules);
     println!();
     println!("Commands:");
     println!("  [p] Play - Start a new game");
     println!("  [a] Run Agent - Watch an AI solve the game");
+    println!("  [r] Watch Replay - Step through a recorded game");
+    println!("  [n] Benchmark - Run an agent over many random worlds");
+    println!("  [v] Arena - Compare every agent on the same random worlds");
     println!("  [q] Quit - Exit the game");
     println!();
     println!("During gameplay:");
@@ -122,6 +426,8 @@ pub fn general_help() {
     println!("  [sh n|s|e|w] - Shoot an arrow in a direction");
     println!("  [cl] Climb out - Exit the cave");
     println!("  [gr] Grab something");
+    println!("  [u] Undo - Take back the last action");
+    println!("  [sr path] Save replay - Record the game so far");
     println!();
     println!("Wumpus (an ugly monster), Pit (a deadly fall)");
     println!("?=");
@@ -131,6 +437,164 @@ pub fn display_env(env: &Environment) {
     println!("{}", env);
 }
 
+/// Prints the environment with the agent's beliefs beside it,
+/// using the same layout. Rooms the agent doesn't know of are
+/// shown as unknown.
+pub fn display_env_with_beliefs(env: &Environment, beliefs: &HashMap<Pos, Belief>) {
+    let nrows = env.grid().nrows();
+    let ncols = env.grid().ncols();
+
+    let header = (0..ncols)
+        .map(|i| i.to_string())
+        .collect::<Vec<_>>()
+        .join("     ");
+    let mut belief_lines = vec![format!("    {}", header)];
+    for i in 0..nrows {
+        let mut line = format!("{}  ", i);
+        for j in 0..ncols {
+            let belief = beliefs.get(&Pos::new(i, j)).unwrap_or(&Belief::Unknown);
+            line.push_str(&format!("{}  ", belief));
+        }
+        belief_lines.push(line);
+    }
+
+    let env_display = env.to_string();
+    let width = env_display.lines().map(|l| l.len()).max().unwrap_or(0);
+    for (env_line, belief_line) in env_display.lines().zip(belief_lines) {
+        println!(
+            "{:width$}  |  {}",
+            env_line,
+            belief_line.trim_end(),
+            width = width
+        );
+    }
+}
+
+pub fn display_seed(seed: u64) {
+    println!("Seed: {}", seed);
+}
+
+pub fn display_inventory(arrows: usize, gold: usize) {
+    println!("Arrows: {}, Gold: {}", arrows, gold);
+}
+
 pub fn display_score(score: isize) {
     println!("Score: {}", score);
 }
+
+/// Prints the statistics of a benchmark.
+pub fn display_bench_report(report: &BenchReport) {
+    println!("Episodes: {}", report.episodes().len());
+    println!("Win rate: {:.1}%", report.win_rate() * 100.0);
+    println!("Deaths:");
+    println!(
+        "  - Pit: {:.1}%",
+        report.outcome_rate(&Outcome::FellIntoPit) * 100.0
+    );
+    println!(
+        "  - Wumpus: {:.1}%",
+        report.outcome_rate(&Outcome::EatenByWumpus) * 100.0
+    );
+    println!(
+        "Empty-handed: {:.1}%",
+        report.outcome_rate(&Outcome::EmptyHanded) * 100.0
+    );
+    println!("Cut short:");
+    println!(
+        "  - Out of steps: {:.1}%",
+        report.outcome_rate(&Outcome::Truncated(Truncation::StepLimit)) * 100.0
+    );
+    println!(
+        "  - Going in circles: {:.1}%",
+        report.outcome_rate(&Outcome::Truncated(Truncation::RepeatedState)) * 100.0
+    );
+    println!(
+        "Score: mean {:.1}, median {:.1}",
+        report.mean_score(),
+        report.median_score()
+    );
+    println!("Mean steps: {:.1}", report.mean_steps());
+    println!(
+        "Mean inference time: {:?} per action",
+        report.mean_inference()
+    );
+}
+
+/// Prints the leaderboard of an arena, the head-to-head results
+/// of its agents, and the `divergent` worlds they disagreed on
+/// the most.
+pub fn display_arena_report(report: &ArenaReport, divergent: usize) {
+    let entries = report.entries();
+    let worlds = entries.first().map_or(0, |e| e.report.episodes().len());
+
+    println!("Leaderboard ({} worlds, 95% confidence):", worlds);
+    println!(
+        "  {:<3} {:<12} {:<20} {:<18} Steps",
+        "#", "Agent", "Mean score", "Win rate"
+    );
+    for (rank, entry) in report.leaderboard().into_iter().enumerate() {
+        let stats = &entry.report;
+        println!(
+            "  {:<3} {:<12} {:<20} {:<18} {:.1}",
+            rank + 1,
+            entry.name,
+            format!("{:.1} ± {:.1}", stats.mean_score(), stats.score_margin()),
+            format!(
+                "{:.1}% ± {:.1}%",
+                stats.win_rate() * 100.0,
+                stats.win_rate_margin() * 100.0
+            ),
+            stats.mean_steps()
+        );
+    }
+
+    println!();
+    println!("Head-to-head (worlds won-lost-tied, row against column):");
+    print!("  {:<12}", "");
+    for entry in entries {
+        print!(" {:<12}", entry.name);
+    }
+    println!();
+    for (a, entry) in entries.iter().enumerate() {
+        print!("  {:<12}", entry.name);
+        for b in 0..entries.len() {
+            let cell = match a == b {
+                true => "-".to_string(),
+                false => {
+                    let h2h = report.head_to_head(a, b);
+                    format!("{}-{}-{}", h2h.wins, h2h.losses, h2h.ties)
+                }
+            };
+            print!(" {:<12}", cell);
+        }
+        println!();
+    }
+
+    println!();
+    let worlds = report.divergent_worlds(divergent);
+    if worlds.is_empty() {
+        println!("The agents scored the same on every world.");
+        return;
+    }
+    println!("Most divergent worlds:");
+    for world in worlds {
+        let scores = entries
+            .iter()
+            .zip(&world.episodes)
+            .map(|(entry, episode)| {
+                format!(
+                    "{} {} ({})",
+                    entry.name,
+                    episode.score,
+                    outcome_label(&episode.outcome)
+                )
+            })
+            .collect::<Vec<_>>()
+            .join(", ");
+        println!(
+            "  Seed {} (spread {}): {}",
+            world.seed, world.spread, scores
+        );
+    }
+    println!("Watch an agent on one of them with --mode agent --seed <seed> --agent <agent>");
+}
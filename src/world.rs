@@ -0,0 +1,544 @@
+use std::{collections::HashSet, fmt::Display, ops::RangeInclusive};
+
+use rand::{
+    Rng, SeedableRng,
+    rngs::StdRng,
+    seq::{IndexedRandom, SliceRandom},
+};
+
+use crate::{
+    grid::{Grid, Pos},
+    room::RoomKind,
+};
+
+/// How pits are scattered over the world.
+#[derive(Debug, Clone, PartialEq)]
+pub enum PitPolicy {
+    /// Each candidate room holds a pit with this probability
+    Probability(f64),
+    /// Exactly this many rooms hold a pit
+    Count(usize),
+}
+
+/// Where the agent starts.
+#[derive(Debug, Clone, PartialEq)]
+pub enum StartPolicy {
+    /// Any room of the world
+    Random,
+    /// Always the given room
+    Fixed(Pos),
+}
+
+/// How strongly a generated world must be solvable.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Solvability {
+    /// Any world is accepted
+    Any,
+    /// Every gold piece can be reached from the start through
+    /// rooms without pits or the Wumpus, without having to kill
+    /// it
+    Reachable,
+    /// Every gold piece can be reached from the start moving
+    /// only into rooms proven safe from the percepts gathered
+    /// along the way
+    Provable,
+}
+
+/// Parameters driving random world generation. Every world holds
+/// a single Wumpus, as the rules of the game have it.
+///
+/// The start room and its neighbors are always kept free of
+/// pits, the Wumpus and gold.
+#[derive(Debug, Clone, PartialEq)]
+pub struct WorldConfig {
+    /// Range the number of rows is drawn from, a single value
+    /// range for an exact size
+    pub rows: RangeInclusive<usize>,
+    /// Range the number of columns is drawn from
+    pub cols: RangeInclusive<usize>,
+    /// How pits are scattered
+    pub pits: PitPolicy,
+    /// Number of gold pieces, at least one
+    pub gold: usize,
+    /// Where the agent starts
+    pub start: StartPolicy,
+    /// Worlds failing this check are rejected and drawn again, up
+    /// to `MAX_ATTEMPTS` times
+    pub solvability: Solvability,
+}
+
+/// Number of worlds drawn before giving up on finding one that
+/// meets the required `Solvability`.
+pub const MAX_ATTEMPTS: usize = 1000;
+
+/// Reasons a `WorldConfig` cannot produce any world.
+#[derive(Debug, Clone, PartialEq)]
+pub enum ConfigError {
+    /// The range of rows or columns is empty
+    EmptySizeRange,
+    /// The world would have no rooms at all
+    ZeroSize,
+    /// The pit probability isn't within `[0, 1]`
+    InvalidPitProbability(f64),
+    /// The world would have no gold
+    NoGold,
+    /// The fixed start position lies outside the smallest world
+    StartOutOfBounds(Pos),
+    /// The smallest world doesn't have enough free rooms for all
+    /// the pits, the Wumpus and gold
+    NotEnoughRooms {
+        /// Rooms the pits, the Wumpus and gold need
+        needed: usize,
+        /// Free rooms in the smallest world
+        available: usize,
+    },
+    /// No world drawn met the required solvability
+    Unsolvable {
+        /// Number of worlds drawn
+        attempts: usize,
+    },
+}
+
+impl Display for ConfigError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ConfigError::EmptySizeRange => f.write_str("size range is empty"),
+            ConfigError::ZeroSize => f.write_str("world must have at least one row and column"),
+            ConfigError::InvalidPitProbability(prob) => f.write_fmt(format_args!(
+                "pit probability {} is not within [0, 1]",
+                prob
+            )),
+            ConfigError::NoGold => f.write_str("a world needs at least one gold piece"),
+            ConfigError::StartOutOfBounds(pos) => f.write_fmt(format_args!(
+                "start position ({}, {}) is outside the world",
+                pos.row, pos.col
+            )),
+            ConfigError::NotEnoughRooms { needed, available } => f.write_fmt(format_args!(
+                "{} rooms are needed for pits, the Wumpus and gold, but only {} are free",
+                needed, available
+            )),
+            ConfigError::Unsolvable { attempts } => f.write_fmt(format_args!(
+                "no solvable world was found in {} attempts",
+                attempts
+            )),
+        }
+    }
+}
+
+impl std::error::Error for ConfigError {}
+
+impl Default for WorldConfig {
+    fn default() -> Self {
+        Self {
+            rows: 4..=6,
+            cols: 4..=6,
+            pits: PitPolicy::Probability(0.2),
+            gold: 1,
+            start: StartPolicy::Random,
+            solvability: Solvability::Any,
+        }
+    }
+}
+
+impl WorldConfig {
+    /// A config for worlds of exactly `nrows` by `ncols` rooms,
+    /// with the other parameters left to their defaults.
+    pub fn with_size(nrows: usize, ncols: usize) -> Self {
+        Self {
+            rows: nrows..=nrows,
+            cols: ncols..=ncols,
+            ..Self::default()
+        }
+    }
+
+    /// Checks that every world this config may draw follows the
+    /// rules of the game, with some gold, and can hold all of its
+    /// pits, the Wumpus and gold outside the start room and its
+    /// neighbors.
+    pub fn validate(&self) -> Result<(), ConfigError> {
+        if self.rows.is_empty() || self.cols.is_empty() {
+            return Err(ConfigError::EmptySizeRange);
+        }
+
+        let nrows = *self.rows.start();
+        let ncols = *self.cols.start();
+        if nrows == 0 || ncols == 0 {
+            return Err(ConfigError::ZeroSize);
+        }
+
+        if let PitPolicy::Probability(prob) = self.pits
+            && !(0.0..=1.0).contains(&prob)
+        {
+            return Err(ConfigError::InvalidPitProbability(prob));
+        }
+
+        if self.gold == 0 {
+            return Err(ConfigError::NoGold);
+        }
+
+        let start_zone = match &self.start {
+            StartPolicy::Random => 1 + (nrows - 1).min(2) + (ncols - 1).min(2),
+            StartPolicy::Fixed(pos) => {
+                if pos.row >= nrows || pos.col >= ncols {
+                    return Err(ConfigError::StartOutOfBounds(pos.clone()));
+                }
+                start_zone(pos, nrows, ncols).len()
+            }
+        };
+
+        let needed = 1
+            + self.gold
+            + match self.pits {
+                PitPolicy::Probability(_) => 0,
+                PitPolicy::Count(count) => count,
+            };
+        let available = nrows * ncols - start_zone;
+        if needed > available {
+            return Err(ConfigError::NotEnoughRooms { needed, available });
+        }
+
+        Ok(())
+    }
+
+    /// Generates a random world after validating the config,
+    /// drawing every choice from a generator seeded with `seed`
+    /// so that the same seed always yields the same world. Worlds
+    /// that aren't solvable enough are rejected and drawn again
+    /// from the same generator.
+    pub fn generate(&self, seed: u64) -> Result<(Grid, Pos), ConfigError> {
+        self.validate()?;
+
+        let mut rng = StdRng::seed_from_u64(seed);
+        for _ in 0..MAX_ATTEMPTS {
+            let (grid, init_pos) = self.draw(&mut rng);
+            let solvable = match self.solvability {
+                Solvability::Any => true,
+                Solvability::Reachable => is_reachable(&grid, &init_pos),
+                Solvability::Provable => is_provable(&grid, &init_pos),
+            };
+            if solvable {
+                return Ok((grid, init_pos));
+            }
+        }
+
+        Err(ConfigError::Unsolvable {
+            attempts: MAX_ATTEMPTS,
+        })
+    }
+
+    fn draw(&self, rng: &mut StdRng) -> (Grid, Pos) {
+        let nrows: usize = rng.random_range(self.rows.clone());
+        let ncols: usize = rng.random_range(self.cols.clone());
+
+        let mut grid = Grid::new(nrows, ncols);
+
+        /* Initialize all rooms with type `RoomKind::Void` */
+        // cp_vec: Candidate Positions, kept in row-major order
+        // so that the same seed always yields the same world
+        let mut cp_vec: Vec<Pos> = Vec::new();
+        for i in 0..nrows {
+            for j in 0..ncols {
+                cp_vec.push(Pos::new(i, j));
+                grid[i][j].set_kind(RoomKind::Void);
+            }
+        }
+
+        /* Choose the start position */
+        let init_pos = match &self.start {
+            StartPolicy::Random => cp_vec.choose(rng).unwrap().clone(),
+            StartPolicy::Fixed(pos) => pos.clone(),
+        };
+        let start_zone = start_zone(&init_pos, nrows, ncols);
+        cp_vec.retain(|pos| !start_zone.contains(pos));
+
+        /* Choose random positions that are not around the start
+        position to put `RoomKind::Pit` rooms */
+        let mut pits: Vec<Pos> = match self.pits {
+            PitPolicy::Probability(prob) => cp_vec
+                .iter()
+                .filter(|_| rng.random_bool(prob))
+                .cloned()
+                .collect(),
+            PitPolicy::Count(count) => cp_vec.choose_multiple(rng, count).cloned().collect(),
+        };
+        // Leave enough room for the Wumpus and gold, dropping pits
+        // at random rather than the last ones in row-major order
+        let max_pits = cp_vec.len() - 1 - self.gold;
+        if pits.len() > max_pits {
+            pits.shuffle(rng);
+            pits.truncate(max_pits);
+        }
+        for pos in pits.iter() {
+            grid.mut_room_at(pos).set_kind(RoomKind::Pit);
+        }
+        cp_vec.retain(|pos| !pits.contains(pos));
+
+        /* Choose random positions that don't contain
+        a pit to put the Wumpus, then the Gold! */
+        for (kind, count) in [(RoomKind::Wumpus, 1), (RoomKind::Gold, self.gold)] {
+            for _ in 0..count {
+                let pos = cp_vec.choose(rng).unwrap().clone();
+                grid.mut_room_at(&pos).set_kind(kind.clone());
+                cp_vec.retain(|p| *p != pos);
+            }
+        }
+
+        (grid, init_pos)
+    }
+}
+
+/// The start room along with its neighbors.
+fn start_zone(start: &Pos, nrows: usize, ncols: usize) -> HashSet<Pos> {
+    (0..nrows)
+        .flat_map(|i| (0..ncols).map(move |j| Pos::new(i, j)))
+        .filter(|pos| pos.row.abs_diff(start.row) + pos.col.abs_diff(start.col) <= 1)
+        .collect()
+}
+
+/// Rooms next to `pos`, within a grid of `nrows` by `ncols`.
+fn neighbors(pos: &Pos, nrows: usize, ncols: usize) -> Vec<Pos> {
+    let mut neighbors = Vec::new();
+    if pos.row > 0 {
+        neighbors.push(Pos::new(pos.row - 1, pos.col));
+    }
+    if pos.row < nrows - 1 {
+        neighbors.push(Pos::new(pos.row + 1, pos.col));
+    }
+    if pos.col > 0 {
+        neighbors.push(Pos::new(pos.row, pos.col - 1));
+    }
+    if pos.col < ncols - 1 {
+        neighbors.push(Pos::new(pos.row, pos.col + 1));
+    }
+    neighbors
+}
+
+fn gold_positions(grid: &Grid) -> Vec<Pos> {
+    (0..grid.nrows())
+        .flat_map(|i| (0..grid.ncols()).map(move |j| Pos::new(i, j)))
+        .filter(|pos| *grid.room_at(pos).get_kind() == RoomKind::Gold)
+        .collect()
+}
+
+/// Whether every gold piece can be reached from `start` through
+/// rooms without pits or the Wumpus. The path never relies on
+/// killing the Wumpus, since the arrow may miss.
+pub fn is_reachable(grid: &Grid, start: &Pos) -> bool {
+    let mut reached: HashSet<Pos> = HashSet::from([start.clone()]);
+    let mut queue = vec![start.clone()];
+    while let Some(pos) = queue.pop() {
+        for next in neighbors(&pos, grid.nrows(), grid.ncols()) {
+            let blocked = matches!(
+                grid.room_at(&next).get_kind(),
+                RoomKind::Pit | RoomKind::Wumpus
+            );
+            if !blocked && reached.insert(next.clone()) {
+                queue.push(next);
+            }
+        }
+    }
+
+    gold_positions(grid).iter().all(|pos| reached.contains(pos))
+}
+
+/// Whether every gold piece can be reached from `start` by an
+/// agent that only moves into rooms it has proven safe. A room
+/// is proven free of pits once a visited neighbor has no breeze,
+/// and free of Wumpuses once a visited neighbor has no stench.
+/// This is a conservative check: an agent reasoning further may
+/// prove more rooms safe, never fewer.
+pub fn is_provable(grid: &Grid, start: &Pos) -> bool {
+    let (nrows, ncols) = (grid.nrows(), grid.ncols());
+    let senses = |pos: &Pos, kind: RoomKind| {
+        neighbors(pos, nrows, ncols)
+            .iter()
+            .any(|n| *grid.room_at(n).get_kind() == kind)
+    };
+
+    let mut visited: HashSet<Pos> = HashSet::new();
+    let mut pit_free: HashSet<Pos> = HashSet::new();
+    let mut wumpus_free: HashSet<Pos> = HashSet::new();
+    let mut queue = vec![start.clone()];
+    while let Some(pos) = queue.pop() {
+        if !visited.insert(pos.clone()) {
+            continue;
+        }
+
+        let breeze = senses(&pos, RoomKind::Pit);
+        let stench = senses(&pos, RoomKind::Wumpus);
+        for next in neighbors(&pos, nrows, ncols) {
+            if !breeze {
+                pit_free.insert(next.clone());
+            }
+            if !stench {
+                wumpus_free.insert(next.clone());
+            }
+            if pit_free.contains(&next) && wumpus_free.contains(&next) && !visited.contains(&next) {
+                queue.push(next);
+            }
+        }
+    }
+
+    gold_positions(grid).iter().all(|pos| visited.contains(pos))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// The kind of every room of `grid`, row by row.
+    fn kinds(grid: &Grid) -> Vec<Vec<RoomKind>> {
+        (0..grid.nrows())
+            .map(|i| grid[i].iter().map(|room| room.get_kind().clone()).collect())
+            .collect()
+    }
+
+    fn count(grid: &Grid, kind: RoomKind) -> usize {
+        kinds(grid).iter().flatten().filter(|k| **k == kind).count()
+    }
+
+    #[test]
+    fn draws_the_same_world_from_the_same_seed() {
+        let config = WorldConfig {
+            solvability: Solvability::Reachable,
+            ..WorldConfig::default()
+        };
+        for seed in 0..20 {
+            let (grid, start) = config.generate(seed).unwrap();
+            let (again, again_start) = config.generate(seed).unwrap();
+            assert_eq!(kinds(&grid), kinds(&again), "seed {}", seed);
+            assert_eq!(start, again_start, "seed {}", seed);
+        }
+
+        let first = kinds(&config.generate(0).unwrap().0);
+        assert!((1..20).any(|seed| kinds(&config.generate(seed).unwrap().0) != first));
+    }
+
+    #[test]
+    fn draws_worlds_following_the_rules() {
+        let config = WorldConfig {
+            pits: PitPolicy::Count(3),
+            gold: 2,
+            start: StartPolicy::Fixed(Pos::new(0, 0)),
+            ..WorldConfig::with_size(5, 4)
+        };
+        for seed in 0..20 {
+            let (grid, start) = config.generate(seed).unwrap();
+            assert_eq!((grid.nrows(), grid.ncols()), (5, 4));
+            assert_eq!(start, Pos::new(0, 0));
+            assert_eq!(count(&grid, RoomKind::Wumpus), 1);
+            assert_eq!(count(&grid, RoomKind::Gold), 2);
+            assert_eq!(count(&grid, RoomKind::Pit), 3);
+            for pos in start_zone(&start, 5, 4) {
+                assert_eq!(*grid.room_at(&pos).get_kind(), RoomKind::Void);
+            }
+        }
+    }
+
+    #[test]
+    fn drops_pits_to_make_room_for_the_wumpus_and_gold() {
+        let config = WorldConfig {
+            pits: PitPolicy::Probability(1.0),
+            start: StartPolicy::Fixed(Pos::new(0, 0)),
+            ..WorldConfig::with_size(3, 3)
+        };
+        let (grid, _) = config.generate(7).unwrap();
+        assert_eq!(count(&grid, RoomKind::Wumpus), 1);
+        assert_eq!(count(&grid, RoomKind::Gold), 1);
+        assert_eq!(count(&grid, RoomKind::Pit), 4);
+    }
+
+    /// The grid and start room of the map `text`.
+    fn world(text: &str) -> (Grid, Pos) {
+        let env = crate::map::Map::parse(text).unwrap().into_environment();
+        (env.grid().clone(), env.start_position().clone())
+    }
+
+    #[test]
+    fn rejects_gold_behind_the_wumpus() {
+        let (grid, start) = world("A _ W G\n");
+        assert!(!is_reachable(&grid, &start));
+        assert!(!is_provable(&grid, &start));
+
+        // A way around the Wumpus
+        let (grid, start) = world("_ _ _ _\nA _ W G\n");
+        assert!(is_reachable(&grid, &start));
+    }
+
+    #[test]
+    fn accepts_gold_behind_safe_rooms_only_when_provably_safe() {
+        let (grid, start) = world("A _ G\n_ _ _\n_ _ W\n");
+        assert!(is_reachable(&grid, &start));
+        assert!(is_provable(&grid, &start));
+
+        // The breeze in the start room leaves no room proven safe
+        let (grid, start) = world("A _ G\nP _ _\n_ _ W\n");
+        assert!(is_reachable(&grid, &start));
+        assert!(!is_provable(&grid, &start));
+    }
+
+    #[test]
+    fn rejects_configs_breaking_the_rules() {
+        let fixed = |row, col| StartPolicy::Fixed(Pos::new(row, col));
+        for (config, error) in [
+            (
+                WorldConfig {
+                    #[allow(clippy::reversed_empty_ranges)]
+                    rows: 5..=4,
+                    ..WorldConfig::default()
+                },
+                ConfigError::EmptySizeRange,
+            ),
+            (WorldConfig::with_size(0, 4), ConfigError::ZeroSize),
+            (
+                WorldConfig {
+                    pits: PitPolicy::Probability(1.5),
+                    ..WorldConfig::default()
+                },
+                ConfigError::InvalidPitProbability(1.5),
+            ),
+            (
+                WorldConfig {
+                    gold: 0,
+                    ..WorldConfig::default()
+                },
+                ConfigError::NoGold,
+            ),
+            (
+                WorldConfig {
+                    start: fixed(4, 0),
+                    ..WorldConfig {
+                        rows: 4..=6,
+                        ..WorldConfig::default()
+                    }
+                },
+                ConfigError::StartOutOfBounds(Pos::new(4, 0)),
+            ),
+            (
+                // The start room and its two neighbors leave a
+                // single room free
+                WorldConfig {
+                    start: fixed(0, 0),
+                    ..WorldConfig::with_size(2, 2)
+                },
+                ConfigError::NotEnoughRooms {
+                    needed: 2,
+                    available: 1,
+                },
+            ),
+            (
+                WorldConfig {
+                    pits: PitPolicy::Count(12),
+                    ..WorldConfig::with_size(4, 4)
+                },
+                ConfigError::NotEnoughRooms {
+                    needed: 14,
+                    available: 11,
+                },
+            ),
+        ] {
+            assert_eq!(config.validate(), Err(error.clone()));
+            assert!(matches!(config.generate(1), Err(err) if err == error));
+        }
+    }
+}